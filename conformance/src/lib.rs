@@ -0,0 +1,246 @@
+//! The example Intcode programs from the puzzle texts of days 2, 5, 7 and 9,
+//! with the inputs they take and what they should produce. Each day's VM
+//! runs the suite from its tests so we can see which VM supports what.
+
+use std::fmt::{Display, Formatter, Error};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Feature {
+    /// ADD, MUL and HALT in position mode.
+    AddMul,
+    /// INPUT and OUTPUT.
+    Io,
+    /// Immediate mode parameters, including negative numbers.
+    ParameterModes,
+    /// LESS_THAN and EQUALS.
+    Compare,
+    /// JUMP_TRUE and JUMP_FALSE.
+    Jump,
+    /// Relative mode and ADJUST_BASE_OFFSET.
+    Relative,
+    /// Reads and writes beyond the end of the program.
+    ExtraMemory,
+    /// Values that don't fit in 32 bits.
+    LargeNumbers
+}
+
+pub const ALL_FEATURES: [Feature; 8] = [
+    Feature::AddMul, Feature::Io, Feature::ParameterModes, Feature::Compare,
+    Feature::Jump, Feature::Relative, Feature::ExtraMemory, Feature::LargeNumbers
+];
+
+#[derive(Debug)]
+pub struct Case {
+    pub name: &'static str,
+    pub day: u32,
+    pub features: &'static [Feature],
+    pub program: &'static [i64],
+    pub inputs: &'static [i64],
+    pub outputs: &'static [i64],
+    /// The start of memory once the program halts.
+    pub memory: Option<&'static [i64]>
+}
+
+impl Case {
+    pub fn needs_only(&self, supported: &[Feature]) -> bool {
+        self.features.iter().all(|f| supported.contains(f))
+    }
+}
+
+/// What a VM could tell us about a run. Not every VM can report everything.
+#[derive(Debug, Default, PartialEq)]
+pub struct Outcome {
+    /// Every output, in order.
+    pub outputs: Option<Vec<i64>>,
+    /// Just the final output, for VMs that only keep the most recent one.
+    pub last_output: Option<i64>,
+    /// Memory once the program halts.
+    pub memory: Option<Vec<i64>>
+}
+
+impl Outcome {
+    fn check(&self, case: &Case) -> Status {
+        let mut checked = false;
+
+        if let Some(outputs) = &self.outputs {
+            if outputs.as_slice() != case.outputs {
+                return Status::Failed(format!("outputs were {:?}, expected {:?}", outputs, case.outputs));
+            }
+            checked |= !case.outputs.is_empty();
+        } else if let (Some(last), Some(expected)) = (self.last_output, case.outputs.last()) {
+            if last != *expected {
+                return Status::Failed(format!("last output was {}, expected {}", last, expected));
+            }
+            checked = true;
+        }
+
+        if let (Some(memory), Some(expected)) = (&self.memory, case.memory) {
+            if !memory.starts_with(expected) {
+                return Status::Failed(format!("memory was {:?}, expected {:?}", memory, expected));
+            }
+            checked = true;
+        }
+
+        if checked { Status::Passed } else { Status::Unverified }
+    }
+}
+
+use Feature::*;
+
+pub const CASES: &[Case] = &[
+    Case { name: "add", day: 2, features: &[AddMul], program: &[1,0,0,0,99], inputs: &[], outputs: &[], memory: Some(&[2,0,0,0,99]) },
+    Case { name: "mul", day: 2, features: &[AddMul], program: &[2,3,0,3,99], inputs: &[], outputs: &[], memory: Some(&[2,3,0,6,99]) },
+    Case { name: "mul past halt", day: 2, features: &[AddMul], program: &[2,4,4,5,99,0], inputs: &[], outputs: &[], memory: Some(&[2,4,4,5,99,9801]) },
+    Case { name: "self modifying", day: 2, features: &[AddMul], program: &[1,1,1,4,99,5,6,0,99], inputs: &[], outputs: &[], memory: Some(&[30,1,1,4,2,5,6,0,99]) },
+    Case { name: "walkthrough", day: 2, features: &[AddMul], program: &[1,9,10,3,2,3,11,0,99,30,40,50], inputs: &[], outputs: &[], memory: Some(&[3500,9,10,70,2,3,11,0,99,30,40,50]) },
+
+    Case { name: "echo", day: 5, features: &[Io], program: &[3,0,4,0,99], inputs: &[42], outputs: &[42], memory: Some(&[42,0,4,0,99]) },
+    Case { name: "immediate mul", day: 5, features: &[AddMul, ParameterModes], program: &[1002,4,3,4,33], inputs: &[], outputs: &[], memory: Some(&[1002,4,3,4,99]) },
+    Case { name: "negative immediate", day: 5, features: &[AddMul, ParameterModes], program: &[1101,100,-1,4,0], inputs: &[], outputs: &[], memory: Some(&[1101,100,-1,4,99]) },
+    Case { name: "equal to 8 (position)", day: 5, features: &[Io, Compare], program: &[3,9,8,9,10,9,4,9,99,-1,8], inputs: &[8], outputs: &[1], memory: None },
+    Case { name: "not equal to 8 (position)", day: 5, features: &[Io, Compare], program: &[3,9,8,9,10,9,4,9,99,-1,8], inputs: &[7], outputs: &[0], memory: None },
+    Case { name: "less than 8 (position)", day: 5, features: &[Io, Compare], program: &[3,9,7,9,10,9,4,9,99,-1,8], inputs: &[5], outputs: &[1], memory: None },
+    Case { name: "not less than 8 (position)", day: 5, features: &[Io, Compare], program: &[3,9,7,9,10,9,4,9,99,-1,8], inputs: &[9], outputs: &[0], memory: None },
+    Case { name: "equal to 8 (immediate)", day: 5, features: &[Io, Compare, ParameterModes], program: &[3,3,1108,-1,8,3,4,3,99], inputs: &[8], outputs: &[1], memory: None },
+    Case { name: "not less than 8 (immediate)", day: 5, features: &[Io, Compare, ParameterModes], program: &[3,3,1107,-1,8,3,4,3,99], inputs: &[9], outputs: &[0], memory: None },
+    Case { name: "zero jump (position)", day: 5, features: &[Io, Jump], program: &[3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9], inputs: &[0], outputs: &[0], memory: None },
+    Case { name: "non-zero jump (position)", day: 5, features: &[Io, Jump], program: &[3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9], inputs: &[5], outputs: &[1], memory: None },
+    Case { name: "zero jump (immediate)", day: 5, features: &[Io, Jump, ParameterModes], program: &[3,3,1105,-1,9,1101,0,0,12,4,12,99,1], inputs: &[0], outputs: &[0], memory: None },
+    Case { name: "non-zero jump (immediate)", day: 5, features: &[Io, Jump, ParameterModes], program: &[3,3,1105,-1,9,1101,0,0,12,4,12,99,1], inputs: &[3], outputs: &[1], memory: None },
+    Case { name: "below 8", day: 5, features: &[Io, Compare, Jump, ParameterModes], program: LARGER_EXAMPLE, inputs: &[7], outputs: &[999], memory: None },
+    Case { name: "exactly 8", day: 5, features: &[Io, Compare, Jump, ParameterModes], program: LARGER_EXAMPLE, inputs: &[8], outputs: &[1000], memory: None },
+    Case { name: "above 8", day: 5, features: &[Io, Compare, Jump, ParameterModes], program: LARGER_EXAMPLE, inputs: &[9], outputs: &[1001], memory: None },
+
+    Case { name: "amplifier 43210", day: 7, features: &[Io, AddMul, ParameterModes], program: &[3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0], inputs: &[4, 0], outputs: &[4], memory: None },
+    Case { name: "amplifier 54321", day: 7, features: &[Io, AddMul, ParameterModes], program: &[3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0], inputs: &[1, 5], outputs: &[54], memory: None },
+    Case { name: "amplifier 65210", day: 7, features: &[Io, AddMul, Compare, ParameterModes], program: &[3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0], inputs: &[1, 0], outputs: &[6], memory: None },
+
+    Case { name: "quine", day: 9, features: &[Io, AddMul, Compare, Jump, ParameterModes, Relative, ExtraMemory], program: QUINE, inputs: &[], outputs: QUINE, memory: None },
+    Case { name: "sixteen digit", day: 9, features: &[Io, AddMul, ParameterModes, LargeNumbers], program: &[1102,34915192,34915192,7,4,7,99,0], inputs: &[], outputs: &[1219070632396864], memory: None },
+    Case { name: "large number", day: 9, features: &[Io, ParameterModes, LargeNumbers], program: &[104,1125899906842624,99], inputs: &[], outputs: &[1125899906842624], memory: None },
+    Case { name: "relative input", day: 9, features: &[Io, ParameterModes, Relative, ExtraMemory], program: &[109,50,203,0,204,0,99], inputs: &[17], outputs: &[17], memory: None },
+];
+
+const LARGER_EXAMPLE: &[i64] = &[
+    3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,
+    1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,
+    999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99
+];
+
+const QUINE: &[i64] = &[109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
+
+/// How one case went for a particular VM.
+#[derive(Debug, PartialEq)]
+pub enum Status {
+    Passed,
+    Failed(String),
+    /// The program ran, but the VM couldn't show us anything to check.
+    Unverified
+}
+
+pub struct Report {
+    pub vm: String,
+    pub results: Vec<(&'static Case, Status)>
+}
+
+/// Runs every case through `runner`. A runner that panics fails that case
+/// rather than the whole suite.
+pub fn run_suite<F>(vm: &str, runner: F) -> Report
+    where F: Fn(&Case) -> Result<Outcome, String> {
+    let results = CASES.iter()
+        .map(|case| {
+            let status = match catch_unwind(AssertUnwindSafe(|| runner(case))) {
+                Ok(Ok(outcome)) => outcome.check(case),
+                Ok(Err(e)) => Status::Failed(e),
+                Err(_) => Status::Failed("panicked".to_string())
+            };
+            (case, status)
+        })
+        .collect();
+    Report { vm: vm.to_string(), results }
+}
+
+impl Report {
+    /// Features exercised by at least one passing case.
+    pub fn supported_features(&self) -> Vec<Feature> {
+        ALL_FEATURES.iter()
+            .cloned()
+            .filter(|feature| self.results.iter()
+                .filter(|(case, _)| case.features.contains(feature))
+                .any(|(_, status)| matches!(status, Status::Passed)))
+            .collect()
+    }
+
+    /// The cases that failed, as "day <day> <name>".
+    pub fn failures(&self) -> Vec<String> {
+        self.results.iter()
+            .filter(|(_, status)| matches!(status, Status::Failed(_)))
+            .map(|(case, _)| format!("day {} {}", case.day, case.name))
+            .collect()
+    }
+
+    /// Panics with the failures among the cases that only need `supported`.
+    pub fn assert_supports(&self, supported: &[Feature]) {
+        let failures: Vec<String> = self.results.iter()
+            .filter(|(case, _)| case.needs_only(supported))
+            .filter_map(|(case, status)| match status {
+                Status::Failed(e) => Some(format!("day {} {}: {}", case.day, case.name, e)),
+                _ => None
+            })
+            .collect();
+        assert!(failures.is_empty(), "{} failed:\n{}", self.vm, failures.join("\n"));
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        writeln!(f, "{} conformance", self.vm)?;
+        for (case, status) in &self.results {
+            match status {
+                Status::Passed => writeln!(f, "  pass  day {} {}", case.day, case.name)?,
+                Status::Failed(e) => writeln!(f, "  FAIL  day {} {}: {}", case.day, case.name, e)?,
+                Status::Unverified => writeln!(f, "  ????  day {} {}: nothing observable", case.day, case.name)?
+            }
+        }
+        writeln!(f, "  supports {:?}", self.supported_features())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_outputs_and_memory() {
+        let case = &CASES[0];
+        let good = Outcome { outputs: Some(vec![]), last_output: None, memory: Some(vec![2, 0, 0, 0, 99]) };
+        assert_eq!(good.check(case), Status::Passed);
+
+        let bad_memory = Outcome { memory: Some(vec![1, 0, 0, 0, 99]), ..Outcome::default() };
+        assert!(matches!(bad_memory.check(case), Status::Failed(_)));
+
+        let no_memory = Outcome { outputs: Some(vec![]), ..Outcome::default() };
+        assert_eq!(no_memory.check(case), Status::Unverified);
+
+        let echo = &CASES[5];
+        assert_eq!(Outcome { last_output: Some(42), ..Outcome::default() }.check(echo), Status::Passed);
+        assert!(matches!(Outcome { outputs: Some(vec![42, 1]), ..Outcome::default() }.check(echo), Status::Failed(_)));
+    }
+
+    #[test]
+    fn panics_fail_a_single_case() {
+        let report = run_suite("panicky", |case| {
+            if case.features.contains(&Relative) {
+                panic!("boom");
+            }
+            Ok(Outcome { outputs: Some(case.outputs.to_vec()), last_output: None, memory: case.memory.map(<[i64]>::to_vec) })
+        });
+        for (case, status) in &report.results {
+            assert_eq!(matches!(status, Status::Failed(_)), case.features.contains(&Relative), "{}", case.name);
+        }
+        report.assert_supports(&[AddMul, Io, ParameterModes, Compare, Jump, LargeNumbers]);
+        assert!(!report.supported_features().contains(&Relative));
+        assert!(report.supported_features().contains(&AddMul));
+    }
+}
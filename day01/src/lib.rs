@@ -0,0 +1,205 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::error::Error;
+use std::str::FromStr;
+
+/// The unsigned integer types masses can be measured in.
+pub trait Mass: Copy + Ord + Default + Display + Debug + FromStr {
+    const TWO: Self;
+    const THREE: Self;
+
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn saturating_sub(self, other: Self) -> Self;
+    fn div(self, other: Self) -> Self;
+}
+
+macro_rules! impl_mass {
+    ($($t:ty),*) => {$(
+        impl Mass for $t {
+            const TWO: Self = 2;
+            const THREE: Self = 3;
+
+            fn checked_add(self, other: Self) -> Option<Self> { <$t>::checked_add(self, other) }
+            fn saturating_sub(self, other: Self) -> Self { <$t>::saturating_sub(self, other) }
+            fn div(self, other: Self) -> Self { self / other }
+        }
+    )*}
+}
+
+impl_mass!(u64, u128);
+
+/// Fuel needed to launch `mass`, ignoring the mass of the fuel itself.
+pub fn fuel_for_mass<T: Mass>(mass: T) -> T {
+    mass.div(T::THREE).saturating_sub(T::TWO)
+}
+
+/// Fuel needed for `mass` and for all of the fuel added along the way. Each
+/// stage is at most a third of the one before, so the total is always less
+/// than `mass` and can't overflow.
+pub fn total_fuel_for_mass<T: Mass>(mass: T) -> T {
+    let mut total = T::default();
+    let mut fuel = fuel_for_mass(mass);
+    while fuel > T::default() {
+        total = total.checked_add(fuel).expect("fuel is bounded by the mass");
+        fuel = fuel_for_mass(fuel);
+    }
+    total
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FuelError {
+    /// A line that isn't a mass, numbered from 1.
+    Parse { line: usize, content: String },
+    /// The total over all modules doesn't fit in the mass type.
+    Overflow
+}
+
+impl Display for FuelError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FuelError::Parse { line, content } => write!(f, "line {}: '{}' is not a mass", line, content),
+            FuelError::Overflow => write!(f, "total fuel overflowed")
+        }
+    }
+}
+
+impl Error for FuelError {}
+
+/// One mass per line; blank lines are skipped.
+pub fn parse_masses<T: Mass>(input: &str) -> Result<Vec<(usize, T)>, FuelError> {
+    input.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(line, content)| {
+            content.parse()
+                .map(|mass| (line, mass))
+                .map_err(|_| FuelError::Parse { line, content: content.to_string() })
+        })
+        .collect()
+}
+
+/// The fuel for a single module.
+#[derive(Debug, PartialEq)]
+pub struct ModuleFuel<T> {
+    pub line: usize,
+    pub mass: T,
+    pub direct: T,
+    pub fuel_for_fuel: T
+}
+
+impl<T: Mass> ModuleFuel<T> {
+    pub fn new(line: usize, mass: T) -> ModuleFuel<T> {
+        let direct = fuel_for_mass(mass);
+        let fuel_for_fuel = total_fuel_for_mass(direct);
+        ModuleFuel { line, mass, direct, fuel_for_fuel }
+    }
+
+    pub fn total(&self) -> T {
+        self.direct.checked_add(self.fuel_for_fuel).expect("fuel is bounded by the mass")
+    }
+}
+
+/// Fuel for every module, with the overall totals checked for overflow.
+pub struct Breakdown<T> {
+    pub modules: Vec<ModuleFuel<T>>,
+    pub direct: T,
+    pub total: T
+}
+
+impl<T: Mass> Breakdown<T> {
+    pub fn new(masses: &[(usize, T)]) -> Result<Breakdown<T>, FuelError> {
+        let modules: Vec<ModuleFuel<T>> = masses.iter()
+            .map(|&(line, mass)| ModuleFuel::new(line, mass))
+            .collect();
+        let sum = |f: &dyn Fn(&ModuleFuel<T>) -> T| modules.iter()
+            .try_fold(T::default(), |acc, module| acc.checked_add(f(module)))
+            .ok_or(FuelError::Overflow);
+        let direct = sum(&|module| module.direct)?;
+        let total = sum(&|module| module.total())?;
+        Ok(Breakdown { modules, direct, total })
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("line,mass,direct_fuel,fuel_for_fuel,total_fuel\n");
+        for m in &self.modules {
+            csv.push_str(&format!("{},{},{},{},{}\n", m.line, m.mass, m.direct, m.fuel_for_fuel, m.total()));
+        }
+        csv
+    }
+
+    pub fn to_table(&self) -> String {
+        let header = ["line", "mass", "direct fuel", "fuel for fuel", "total fuel"];
+        let mut rows: Vec<[String; 5]> = self.modules.iter()
+            .map(|m| [m.line.to_string(), m.mass.to_string(), m.direct.to_string(), m.fuel_for_fuel.to_string(), m.total().to_string()])
+            .collect();
+        rows.push(["total".to_string(), String::new(), self.direct.to_string(), String::new(), self.total.to_string()]);
+
+        let widths: Vec<usize> = (0..header.len())
+            .map(|col| rows.iter().map(|row| row[col].len()).chain(Some(header[col].len())).max().unwrap_or(0))
+            .collect();
+        let format_row = |cells: Vec<&str>| cells.iter().zip(&widths)
+            .map(|(cell, &width)| format!("{:>width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join(" | ");
+
+        let mut table = format_row(header.to_vec());
+        table.push('\n');
+        table.push_str(&widths.iter().map(|&w| "-".repeat(w)).collect::<Vec<_>>().join("-+-"));
+        table.push('\n');
+        for row in &rows {
+            table.push_str(&format_row(row.iter().map(String::as_str).collect()));
+            table.push('\n');
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test1() {
+        assert_eq!(fuel_for_mass(12u64), 2);
+        assert_eq!(fuel_for_mass(14u64), 2);
+        assert_eq!(fuel_for_mass(1969u64), 654);
+        assert_eq!(fuel_for_mass(100756u64), 33583);
+        assert_eq!(fuel_for_mass(2u64), 0);
+    }
+    
+    #[test]
+    fn test2() {
+        assert_eq!(total_fuel_for_mass(100756u64), 50346);
+        assert_eq!(total_fuel_for_mass(1969u128), 966);
+        let huge = total_fuel_for_mass(u128::MAX);
+        assert!(huge > u128::MAX / 3 && huge < u128::MAX / 2);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(parse_masses::<u64>("12\n\n14\n"), Ok(vec![(1, 12), (3, 14)]));
+        assert_eq!(parse_masses::<u64>("12\n-4\n"), Err(FuelError::Parse { line: 2, content: "-4".to_string() }));
+    }
+
+    #[test]
+    fn breakdown() {
+        let breakdown = Breakdown::new(&[(1, 14u64), (2, 1969)]).unwrap();
+        assert_eq!(breakdown.modules[1], ModuleFuel { line: 2, mass: 1969, direct: 654, fuel_for_fuel: 312 });
+        assert_eq!(breakdown.direct, 656);
+        assert_eq!(breakdown.total, 968);
+        assert_eq!(breakdown.to_csv(), "line,mass,direct_fuel,fuel_for_fuel,total_fuel\n1,14,2,0,2\n2,1969,654,312,966\n");
+        assert!(Breakdown::new(&[(1, u64::MAX), (2, u64::MAX)]).is_ok());
+        assert_eq!(Breakdown::new(&[(1, u64::MAX); 4]).err(), Some(FuelError::Overflow));
+    }
+
+    #[test]
+    fn table() {
+        let breakdown = Breakdown::new(&[(1, 14u64), (2, 1969)]).unwrap();
+        assert_eq!(breakdown.to_table(), concat!(
+" line | mass | direct fuel | fuel for fuel | total fuel\n",
+"------+------+-------------+---------------+-----------\n",
+"    1 |   14 |           2 |             0 |          2\n",
+"    2 | 1969 |         654 |           312 |        966\n",
+"total |      |         656 |               |        968\n"));
+    }
+}
@@ -1,18 +1,32 @@
 use std::fs;
+use std::env;
 use std::error::Error;
+use crate::symbolic::{Polynomial, Symbol, explore_noun_verb};
+
+pub mod symbolic;
+
+const PART2_TARGET : i32 = 19690720;
+const MAX_INPUT : i32 = 99;
 
 fn main() {
     let input : Vec<i32> = fs::read_to_string("input.txt")
         .expect("Something went wrong reading the file")
-        .split(",")
+        .trim()
+        .split(',')
         .map(|x| x.parse::<i32>().unwrap())
         .collect();
 
     let result = part01(&mut input.clone());
     eprintln!("part01 = {:#?}", result);
 
-    part02(&mut input.clone());
-        
+    let target = env::args().nth(1)
+        .map(|t| t.parse().expect("target should be a number"))
+        .unwrap_or(PART2_TARGET);
+    if let Some(formula) = formula(&input) {
+        eprintln!("position 0 = {}", formula);
+    }
+    let result = part02(&input, target);
+    eprintln!("part02 = {:#?}", result);
 }
 
 type OpCodes = Vec<i32>;
@@ -21,22 +35,109 @@ fn part01(op_codes : &mut OpCodes) -> i32{
     exec_with_init(op_codes, 12, 2).unwrap_or_default()
 }
 
-fn part02(op_codes : &mut OpCodes) {
-    
-    for noun in 1..=100 {
-        for verb in 1..=100 {
-            let mut new_opcode = op_codes.clone();
-            let new_output = exec_with_init(&mut new_opcode, noun, verb);
-            if let Ok(19690720) = new_output {
-                eprintln!("{},{}", noun, verb);
-                break;
-            }
+fn part02(op_codes : &[i32], target: i32) -> Option<i32> {
+    solve(op_codes, target).map(|(noun, verb)| 100 * noun + verb)
+}
+
+/// Position 0 for the given noun and verb, leaving `op_codes` untouched.
+fn output_for(op_codes : &[i32], noun: i32, verb: i32) -> Option<i32> {
+    exec_with_init(&mut op_codes.to_vec(), noun, verb).ok()
+}
+
+/// Position 0 as a polynomial in the noun and verb, if the program has a
+/// single path through it and only adds and multiplies.
+fn formula(op_codes : &[i32]) -> Option<Polynomial> {
+    let paths = explore_noun_verb(op_codes).ok()?;
+    match paths.as_slice() {
+        [path] if path.constraints.is_empty() => Polynomial::from_expr(path.result()),
+        _ => None
+    }
+}
+
+/// The output as `a * noun + b * verb + c`, if the program behaves that way.
+/// Puzzle programs usually do, which lets us solve for a target directly.
+#[derive(Debug, PartialEq)]
+struct Affine {
+    a: i64,
+    b: i64,
+    c: i64
+}
+
+impl Affine {
+    /// Reads the coefficients straight off the symbolic formula.
+    fn from_formula(op_codes : &[i32]) -> Option<Affine> {
+        let formula = formula(op_codes)?;
+        let only_noun_verb = formula.symbols().iter().all(|&s| s == Symbol::Noun || s == Symbol::Verb);
+        if formula.degree() > 1 || !only_noun_verb {
+            return None;
         }
+        Some(Affine {
+            a: formula.coefficient(&[(Symbol::Noun, 1)]),
+            b: formula.coefficient(&[(Symbol::Verb, 1)]),
+            c: formula.coefficient(&[])
+        })
     }
 
+    /// Finds the coefficients by running the program, then checks a few
+    /// more points to make sure it really is affine.
+    fn fit(op_codes : &[i32]) -> Option<Affine> {
+        let output = |noun, verb| output_for(op_codes, noun, verb).map(i64::from);
+        let c = output(0, 0)?;
+        let a = output(1, 0)? - c;
+        let b = output(0, 1)? - c;
+        let affine = Affine { a, b, c };
+
+        let probes = [(1, 1), (MAX_INPUT, 0), (0, MAX_INPUT), (MAX_INPUT, MAX_INPUT), (37, 59), (64, 3)];
+        let fits = probes.iter()
+            .all(|&(noun, verb)| output(noun, verb) == Some(affine.at(noun, verb)));
+        if fits { Some(affine) } else { None }
+    }
+
+    fn at(&self, noun: i32, verb: i32) -> i64 {
+        self.a * i64::from(noun) + self.b * i64::from(verb) + self.c
+    }
+
+    /// Candidate (noun, verb) pairs that hit the target according to the formula.
+    fn candidates(&self, target: i32) -> Vec<(i32, i32)> {
+        let remainder = |noun| i64::from(target) - self.c - self.a * i64::from(noun);
+        (0..=MAX_INPUT)
+            .flat_map(|noun| {
+                if self.b == 0 {
+                    if remainder(noun) == 0 { (0..=MAX_INPUT).map(|verb| (noun, verb)).collect() } else { vec![] }
+                } else if remainder(noun) % self.b == 0 {
+                    let verb = remainder(noun) / self.b;
+                    if (0..=i64::from(MAX_INPUT)).contains(&verb) { vec![(noun, verb as i32)] } else { vec![] }
+                } else {
+                    vec![]
+                }
+            })
+            .collect()
+    }
+}
+
+/// Every (noun, verb) pair that makes the program output `target`.
+fn all_solutions(op_codes : &[i32], target: i32) -> Vec<(i32, i32)> {
+    let candidates = match Affine::from_formula(op_codes).or_else(|| Affine::fit(op_codes)) {
+        Some(affine) => affine.candidates(target),
+        None => (0..=MAX_INPUT)
+            .flat_map(|noun| (0..=MAX_INPUT).map(move |verb| (noun, verb)))
+            .collect()
+    };
+
+    candidates.into_iter()
+        .filter(|&(noun, verb)| output_for(op_codes, noun, verb) == Some(target))
+        .collect()
+}
+
+/// The first (noun, verb) pair, ordered by noun then verb, that outputs `target`.
+fn solve(op_codes : &[i32], target: i32) -> Option<(i32, i32)> {
+    all_solutions(op_codes, target).into_iter().next()
 }
 
 fn exec_with_init(op_codes : &mut OpCodes, noun: i32, verb: i32) -> Result<i32, Box<dyn Error>> {
+    if op_codes.len() < 3 {
+        return Err("Program too short for a noun and verb".into());
+    }
     op_codes[1] = noun;
     op_codes[2] = verb;
     exec(op_codes)
@@ -44,29 +145,36 @@ fn exec_with_init(op_codes : &mut OpCodes, noun: i32, verb: i32) -> Result<i32,
 
 fn exec(op_codes : &mut OpCodes) -> Result<i32, Box<dyn Error>> {
     let mut p = 0;
-    
-    while op_codes[p] != 99 {
-        let result_reg = op_codes[p+3] as usize;
-        let reg_a = op_codes[p+1] as usize;
-        let reg_b = op_codes[p+2] as usize;
-        match op_codes[p] {
-            1 =>  op_codes[result_reg] = op_codes[reg_a] + op_codes[reg_b],
-            2 =>  op_codes[result_reg] = op_codes[reg_a] * op_codes[reg_b],
-            99 => break,
+    let read = |op_codes: &OpCodes, index: usize| -> Result<i32, Box<dyn Error>> {
+        op_codes.get(index).cloned().ok_or_else(|| format!("Address {} out of range", index).into())
+    };
+    let address = |value: i32| -> Result<usize, Box<dyn Error>> {
+        if value < 0 { Err(format!("Negative address {}", value).into()) } else { Ok(value as usize) }
+    };
+
+    while read(op_codes, p)? != 99 {
+        let result_reg = address(read(op_codes, p+3)?)?;
+        let a = read(op_codes, address(read(op_codes, p+1)?)?)?;
+        let b = read(op_codes, address(read(op_codes, p+2)?)?)?;
+        let result = match op_codes[p] {
+            1 =>  a.checked_add(b),
+            2 =>  a.checked_mul(b),
             _ => return Err(format!("Unknown OpCode {}", op_codes[p]).into())
-        }
-        
+        }.ok_or("Arithmetic overflow")?;
+
+        *op_codes.get_mut(result_reg).ok_or_else(|| format!("Address {} out of range", result_reg))? = result;
         p+=4;
-         
     }
-    
+
     Ok(op_codes[0])
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+    use std::convert::TryFrom;
+    use conformance::{Feature, Outcome, run_suite};
+
     #[test]
     fn some_tests() {
         let mut input = vec![1,0,0,0,99];
@@ -82,5 +190,100 @@ mod tests {
         let mut input = vec![1,1,1,4,99,5,6,0,99];
         assert_eq!(exec(&mut input).unwrap(), 30);
     }
-}
 
+    #[test]
+    fn bad_programs_are_errors() {
+        assert!(exec(&mut vec![1,0,0,7,99]).is_err());
+        assert!(exec(&mut vec![1,0,0,0]).is_err());
+        assert!(exec(&mut vec![3,0,0,0,99]).is_err());
+    }
+
+    /// Day 2 programs only add and multiply, and never output anything.
+    #[test]
+    fn conformance() {
+        let report = run_suite("day02 exec", |case| {
+            let mut op_codes = case.program.iter()
+                .map(|&c| i32::try_from(c).map_err(|_| format!("{} doesn't fit in an i32", c)))
+                .collect::<Result<OpCodes, String>>()?;
+            exec(&mut op_codes).map_err(|e| e.to_string())?;
+            let memory = op_codes.into_iter().map(i64::from).collect();
+            Ok(Outcome { outputs: Some(vec![]), last_output: None, memory: Some(memory) })
+        });
+        assert_eq!(report.failures(), vec![
+            "day 5 echo",
+            "day 5 immediate mul",
+            "day 5 negative immediate",
+            "day 5 equal to 8 (position)",
+            "day 5 not equal to 8 (position)",
+            "day 5 less than 8 (position)",
+            "day 5 not less than 8 (position)",
+            "day 5 equal to 8 (immediate)",
+            "day 5 not less than 8 (immediate)",
+            "day 5 zero jump (position)",
+            "day 5 non-zero jump (position)",
+            "day 5 zero jump (immediate)",
+            "day 5 non-zero jump (immediate)",
+            "day 5 below 8",
+            "day 5 exactly 8",
+            "day 5 above 8",
+            "day 7 amplifier 43210",
+            "day 7 amplifier 54321",
+            "day 7 amplifier 65210",
+            "day 9 quine",
+            "day 9 sixteen digit",
+            "day 9 large number",
+            "day 9 relative input"
+        ]);
+        report.assert_supports(&[Feature::AddMul]);
+    }
+
+    /// Position 0 ends up as `3 * noun + verb + 7`.
+    fn affine_program() -> OpCodes {
+        let mut program: OpCodes = vec![0; 100];
+        program[..23].copy_from_slice(&[
+            1, 0, 0, 3,      // junk write, overwritten below
+            2, 1, 20, 21,    // mem[21] = noun * 3
+            1, 21, 2, 21,    // mem[21] += verb
+            1, 21, 22, 0,    // mem[0] = mem[21] + 7
+            99, 0, 0, 0,
+            3, 0, 7]);
+        program
+    }
+
+    #[test]
+    fn solves_affine_program() {
+        let program = affine_program();
+        assert_eq!(Affine::fit(&program), Some(Affine { a: 3, b: 1, c: 7 }));
+        assert_eq!(Affine::from_formula(&program), Some(Affine { a: 3, b: 1, c: 7 }));
+        assert_eq!(formula(&program).unwrap().to_string(), "3*noun + verb + 7");
+        assert_eq!(all_solutions(&program, 6), vec![]);
+        assert_eq!(all_solutions(&program, 60).len(), 18);
+        assert_eq!(solve(&program, 230), Some((42, 97)));
+        assert_eq!(part02(&program, 230), Some(4297));
+    }
+
+    #[test]
+    fn falls_back_to_search() {
+        // mem[0] = mem[noun] * mem[verb] is not affine
+        let mut program: OpCodes = (0..100).collect();
+        program[..4].copy_from_slice(&[2, 0, 0, 0]);
+        program[4] = 99;
+        assert_eq!(Affine::fit(&program), None);
+        assert_eq!(Affine::from_formula(&program), None);
+        assert_eq!(all_solutions(&program, 89 * 97), vec![(89, 97), (97, 89)]);
+    }
+
+    #[test]
+    fn puzzle() {
+        let input : Vec<i32> = fs::read_to_string("input.txt").unwrap()
+            .trim()
+            .split(',')
+            .map(|x| x.parse::<i32>().unwrap())
+            .collect();
+        assert!(Affine::fit(&input).is_some());
+        assert_eq!(Affine::from_formula(&input), Affine::fit(&input));
+        let answer = part02(&input, PART2_TARGET).unwrap();
+        assert_eq!(output_for(&input, answer / 100, answer % 100), Some(PART2_TARGET));
+        assert_eq!(part02(&input, output_for(&input, 12, 2).unwrap()), Some(1202));
+    }
+}
@@ -0,0 +1,499 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Error};
+
+const HALT : i64 = 99;
+const ADD : i64 = 1;
+const MUL : i64 = 2;
+const INPUT : i64 = 3;
+const OUTPUT : i64 = 4;
+const JUMP_TRUE : i64 = 5;
+const JUMP_FALSE : i64 = 6;
+const LESS_THAN : i64 = 7;
+const EQUALS : i64 = 8;
+
+const MAX_STEPS : usize = 100_000;
+const MAX_PATHS : usize = 256;
+
+/// The unknowns a program's behaviour can depend on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Symbol {
+    Noun,
+    Verb,
+    /// The nth value read by an INPUT instruction, counting from 0.
+    Input(usize)
+}
+
+/// The value of a memory cell in terms of the unknowns.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Const(i64),
+    Var(Symbol),
+    Add(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    LessThan(Box<Expr>, Box<Expr>),
+    Equals(Box<Expr>, Box<Expr>),
+    /// Whatever was in memory at an address that is only known symbolically.
+    Load(Box<Expr>)
+}
+
+impl Expr {
+    fn as_const(&self) -> Option<i64> {
+        if let Expr::Const(c) = self { Some(*c) } else { None }
+    }
+
+    pub fn sum(a: Expr, b: Expr) -> Expr {
+        match (a.as_const(), b.as_const()) {
+            (Some(x), Some(y)) if x.checked_add(y).is_some() => Expr::Const(x + y),
+            (Some(0), _) => b,
+            (_, Some(0)) => a,
+            _ => Expr::Add(Box::new(a), Box::new(b))
+        }
+    }
+
+    pub fn product(a: Expr, b: Expr) -> Expr {
+        match (a.as_const(), b.as_const()) {
+            (Some(x), Some(y)) if x.checked_mul(y).is_some() => Expr::Const(x * y),
+            (Some(0), _) | (_, Some(0)) => Expr::Const(0),
+            (Some(1), _) => b,
+            (_, Some(1)) => a,
+            _ => Expr::Mul(Box::new(a), Box::new(b))
+        }
+    }
+
+    pub fn less_than(a: Expr, b: Expr) -> Expr {
+        match (a.as_const(), b.as_const()) {
+            (Some(x), Some(y)) => Expr::Const(if x < y { 1 } else { 0 }),
+            _ if a == b => Expr::Const(0),
+            _ => Expr::LessThan(Box::new(a), Box::new(b))
+        }
+    }
+
+    pub fn equals(a: Expr, b: Expr) -> Expr {
+        match (a.as_const(), b.as_const()) {
+            (Some(x), Some(y)) => Expr::Const(if x == y { 1 } else { 0 }),
+            _ if a == b => Expr::Const(1),
+            _ => Expr::Equals(Box::new(a), Box::new(b))
+        }
+    }
+
+    /// Evaluates the expression, or `None` if it depends on a symbolic load
+    /// or an unbound symbol.
+    pub fn eval(&self, env: &dyn Fn(Symbol) -> Option<i64>) -> Option<i64> {
+        match self {
+            Expr::Const(c) => Some(*c),
+            Expr::Var(s) => env(*s),
+            Expr::Add(a, b) => a.eval(env)?.checked_add(b.eval(env)?),
+            Expr::Mul(a, b) => a.eval(env)?.checked_mul(b.eval(env)?),
+            Expr::LessThan(a, b) => Some(if a.eval(env)? < b.eval(env)? { 1 } else { 0 }),
+            Expr::Equals(a, b) => Some(if a.eval(env)? == b.eval(env)? { 1 } else { 0 }),
+            Expr::Load(_) => None
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            Expr::Const(c) => write!(f, "{}", c),
+            Expr::Var(s) => write!(f, "{}", s),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Mul(a, b) => write!(f, "{} * {}", a, b),
+            Expr::LessThan(a, b) => write!(f, "[{} < {}]", a, b),
+            Expr::Equals(a, b) => write!(f, "[{} == {}]", a, b),
+            Expr::Load(a) => write!(f, "memory[{}]", a)
+        }
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            Symbol::Noun => write!(f, "noun"),
+            Symbol::Verb => write!(f, "verb"),
+            Symbol::Input(n) => write!(f, "input{}", n)
+        }
+    }
+}
+
+/// A monomial is a sorted list of symbols and their powers.
+type Monomial = Vec<(Symbol, u32)>;
+
+/// A sum of monomials with integer coefficients.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Polynomial {
+    terms: BTreeMap<Monomial, i64>
+}
+
+impl Polynomial {
+    pub fn constant(c: i64) -> Polynomial {
+        let mut terms = BTreeMap::new();
+        if c != 0 {
+            terms.insert(vec![], c);
+        }
+        Polynomial { terms }
+    }
+
+    pub fn var(s: Symbol) -> Polynomial {
+        let mut terms = BTreeMap::new();
+        terms.insert(vec![(s, 1)], 1);
+        Polynomial { terms }
+    }
+
+    /// The polynomial for an expression built only from constants, symbols,
+    /// ADD and MUL, or `None` if it has comparisons, loads or overflows.
+    pub fn from_expr(expr: &Expr) -> Option<Polynomial> {
+        match expr {
+            Expr::Const(c) => Some(Polynomial::constant(*c)),
+            Expr::Var(s) => Some(Polynomial::var(*s)),
+            Expr::Add(a, b) => Polynomial::from_expr(a)?.checked_add(&Polynomial::from_expr(b)?),
+            Expr::Mul(a, b) => Polynomial::from_expr(a)?.checked_mul(&Polynomial::from_expr(b)?),
+            _ => None
+        }
+    }
+
+    pub fn checked_add(&self, other: &Polynomial) -> Option<Polynomial> {
+        let mut terms = self.terms.clone();
+        for (monomial, c) in &other.terms {
+            let sum = terms.get(monomial).unwrap_or(&0).checked_add(*c)?;
+            if sum == 0 { terms.remove(monomial); } else { terms.insert(monomial.clone(), sum); }
+        }
+        Some(Polynomial { terms })
+    }
+
+    pub fn checked_mul(&self, other: &Polynomial) -> Option<Polynomial> {
+        let mut product = Polynomial::default();
+        for (m1, c1) in &self.terms {
+            for (m2, c2) in &other.terms {
+                let mut powers: BTreeMap<Symbol, u32> = m1.iter().cloned().collect();
+                for (s, p) in m2 {
+                    *powers.entry(*s).or_insert(0) += p;
+                }
+                let mut term = Polynomial::default();
+                term.terms.insert(powers.into_iter().collect(), c1.checked_mul(*c2)?);
+                product = product.checked_add(&term)?;
+            }
+        }
+        Some(product)
+    }
+
+    pub fn coefficient(&self, monomial: &[(Symbol, u32)]) -> i64 {
+        *self.terms.get(monomial).unwrap_or(&0)
+    }
+
+    pub fn degree(&self) -> u32 {
+        self.terms.keys()
+            .map(|m| m.iter().map(|(_, p)| p).sum())
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn symbols(&self) -> Vec<Symbol> {
+        let mut symbols: Vec<Symbol> = self.terms.keys().flatten().map(|(s, _)| *s).collect();
+        symbols.sort();
+        symbols.dedup();
+        symbols
+    }
+}
+
+impl Display for Polynomial {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        // Highest degree first, constant last
+        let mut terms: Vec<(&Monomial, &i64)> = self.terms.iter().collect();
+        terms.sort_by_key(|(m, _)| std::cmp::Reverse(m.iter().map(|(_, p)| p).sum::<u32>()));
+        for (i, (monomial, c)) in terms.into_iter().enumerate() {
+            let sign = if *c < 0 { "-" } else { "+" };
+            if i == 0 {
+                if *c < 0 { write!(f, "-")?; }
+            } else {
+                write!(f, " {} ", sign)?;
+            }
+            let factors: Vec<String> = monomial.iter()
+                .map(|(s, p)| if *p == 1 { s.to_string() } else { format!("{}^{}", s, p) })
+                .collect();
+            match (c.abs(), factors.is_empty()) {
+                (c, true) => write!(f, "{}", c)?,
+                (1, false) => write!(f, "{}", factors.join("*"))?,
+                (c, false) => write!(f, "{}*{}", c, factors.join("*"))?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A branch condition that must hold for a path to be taken.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constraint {
+    pub condition: Expr,
+    /// Whether the condition is non-zero on this path.
+    pub holds: bool
+}
+
+impl Display for Constraint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{} {} 0", self.condition, if self.holds { "!=" } else { "==" })
+    }
+}
+
+/// One way through the program, and what it computes.
+#[derive(Debug, Clone)]
+pub struct Path {
+    pub constraints: Vec<Constraint>,
+    pub outputs: Vec<Expr>,
+    pub memory: Vec<Expr>
+}
+
+impl Path {
+    /// Position 0 when the program halts, which is the day02 answer.
+    pub fn result(&self) -> &Expr {
+        &self.memory[0]
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SymbolicError {
+    OutOfRange { p: usize, address: i64 },
+    SymbolicOpcode { p: usize },
+    SymbolicWrite { p: usize },
+    SymbolicJump { p: usize },
+    UnknownOpCode { p: usize, op_code: i64 },
+    TooManySteps,
+    TooManyPaths
+}
+
+#[derive(Clone)]
+struct State {
+    memory: Vec<Expr>,
+    p: usize,
+    inputs: usize,
+    steps: usize,
+    constraints: Vec<Constraint>,
+    outputs: Vec<Expr>
+}
+
+enum Step {
+    Continue,
+    Halt,
+    Fork(State, State)
+}
+
+impl State {
+    fn read(&self, address: i64) -> Result<Expr, SymbolicError> {
+        if address < 0 || address as usize >= self.memory.len() {
+            return Err(SymbolicError::OutOfRange { p: self.p, address });
+        }
+        Ok(self.memory[address as usize].clone())
+    }
+
+    fn literal(&self, offset: usize) -> Result<Expr, SymbolicError> {
+        self.read((self.p + offset) as i64)
+    }
+
+    fn mode(&self, offset: usize) -> i64 {
+        let op_code = self.memory[self.p].as_const().unwrap_or(0);
+        (op_code / 10i64.pow(offset as u32 + 1)) % 10
+    }
+
+    fn param(&self, offset: usize) -> Result<Expr, SymbolicError> {
+        let literal = self.literal(offset)?;
+        if self.mode(offset) == 1 {
+            return Ok(literal);
+        }
+        match literal.as_const() {
+            Some(address) => self.read(address),
+            None => Ok(Expr::Load(Box::new(literal)))
+        }
+    }
+
+    fn write(&mut self, offset: usize, value: Expr) -> Result<(), SymbolicError> {
+        let address = self.literal(offset)?.as_const().ok_or(SymbolicError::SymbolicWrite { p: self.p })?;
+        self.read(address)?;
+        self.memory[address as usize] = value;
+        Ok(())
+    }
+
+    fn binary_op(&mut self, f: fn(Expr, Expr) -> Expr) -> Result<Step, SymbolicError> {
+        let value = f(self.param(1)?, self.param(2)?);
+        self.write(3, value)?;
+        self.p += 4;
+        Ok(Step::Continue)
+    }
+
+    fn jump(&mut self, jump_if: bool) -> Result<Step, SymbolicError> {
+        let condition = self.param(1)?;
+        let target = self.param(2)?.as_const().ok_or(SymbolicError::SymbolicJump { p: self.p })?;
+        let (jumped, fell_through) = (target as usize, self.p + 3);
+
+        if let Some(c) = condition.as_const() {
+            self.p = if (c != 0) == jump_if { jumped } else { fell_through };
+            return Ok(Step::Continue);
+        }
+
+        // A condition we've already branched on has to go the same way again
+        if let Some(known) = self.constraints.iter().find(|c| c.condition == condition) {
+            self.p = if known.holds == jump_if { jumped } else { fell_through };
+            return Ok(Step::Continue);
+        }
+
+        let mut when_true = self.clone();
+        when_true.constraints.push(Constraint { condition: condition.clone(), holds: true });
+        when_true.p = if jump_if { jumped } else { fell_through };
+
+        let mut when_false = self.clone();
+        when_false.constraints.push(Constraint { condition, holds: false });
+        when_false.p = if jump_if { fell_through } else { jumped };
+
+        Ok(Step::Fork(when_true, when_false))
+    }
+
+    fn step(&mut self) -> Result<Step, SymbolicError> {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return Err(SymbolicError::TooManySteps);
+        }
+
+        let op_code = self.literal(0)?.as_const().ok_or(SymbolicError::SymbolicOpcode { p: self.p })?;
+        match op_code % 100 {
+            ADD => self.binary_op(Expr::sum),
+            MUL => self.binary_op(Expr::product),
+            LESS_THAN => self.binary_op(Expr::less_than),
+            EQUALS => self.binary_op(Expr::equals),
+            JUMP_TRUE => self.jump(true),
+            JUMP_FALSE => self.jump(false),
+            INPUT => {
+                self.write(1, Expr::Var(Symbol::Input(self.inputs)))?;
+                self.inputs += 1;
+                self.p += 2;
+                Ok(Step::Continue)
+            },
+            OUTPUT => {
+                let value = self.param(1)?;
+                self.outputs.push(value);
+                self.p += 2;
+                Ok(Step::Continue)
+            },
+            HALT => Ok(Step::Halt),
+            _ => Err(SymbolicError::UnknownOpCode { p: self.p, op_code })
+        }
+    }
+}
+
+/// Runs the program with every memory cell as an expression, following both
+/// sides of any branch whose condition isn't known, and returns each path
+/// that reaches HALT.
+pub fn explore(memory: Vec<Expr>) -> Result<Vec<Path>, SymbolicError> {
+    let start = State { memory, p: 0, inputs: 0, steps: 0, constraints: vec![], outputs: vec![] };
+    let mut pending = vec![start];
+    let mut paths = vec![];
+
+    while let Some(mut state) = pending.pop() {
+        loop {
+            match state.step()? {
+                Step::Continue => {},
+                Step::Halt => {
+                    paths.push(Path { constraints: state.constraints, outputs: state.outputs, memory: state.memory });
+                    break;
+                },
+                Step::Fork(a, b) => {
+                    if paths.len() + pending.len() + 2 > MAX_PATHS {
+                        return Err(SymbolicError::TooManyPaths);
+                    }
+                    pending.push(b);
+                    pending.push(a);
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Explores a day02 program with the noun and verb left symbolic.
+pub fn explore_noun_verb(op_codes: &[i32]) -> Result<Vec<Path>, SymbolicError> {
+    let mut memory: Vec<Expr> = op_codes.iter().map(|&c| Expr::Const(i64::from(c))).collect();
+    if memory.len() < 3 {
+        return Err(SymbolicError::OutOfRange { p: 0, address: 2 });
+    }
+    memory[1] = Expr::Var(Symbol::Noun);
+    memory[2] = Expr::Var(Symbol::Verb);
+    explore(memory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn consts(program: &[i64]) -> Vec<Expr> {
+        program.iter().cloned().map(Expr::Const).collect()
+    }
+
+    fn noun_verb(noun: i64, verb: i64) -> impl Fn(Symbol) -> Option<i64> {
+        move |s| match s { Symbol::Noun => Some(noun), Symbol::Verb => Some(verb), _ => None }
+    }
+
+    #[test]
+    fn add_mul_formula() {
+        // mem[0] = (noun + verb) * noun
+        let program = vec![1, 0, 0, 3, 1, 1, 2, 13, 2, 13, 1, 0, 99, 0];
+        let paths = explore_noun_verb(&program).unwrap();
+        assert_eq!(paths.len(), 1);
+
+        let result = paths[0].result();
+        assert_eq!(result.eval(&noun_verb(3, 4)), Some(21));
+
+        let polynomial = Polynomial::from_expr(result).unwrap();
+        assert_eq!(polynomial.to_string(), "noun*verb + noun^2");
+        assert_eq!(polynomial.degree(), 2);
+        assert_eq!(polynomial.symbols(), vec![Symbol::Noun, Symbol::Verb]);
+    }
+
+    #[test]
+    fn symbolic_load_is_harmless_once_overwritten() {
+        // The first instruction reads mem[noun] and mem[verb] before anything sensible happens
+        let program = vec![1, 0, 0, 3, 1, 1, 2, 0, 99];
+        let paths = explore_noun_verb(&program).unwrap();
+        assert_eq!(paths[0].memory[3], Expr::Add(
+            Box::new(Expr::Load(Box::new(Expr::Var(Symbol::Noun)))),
+            Box::new(Expr::Load(Box::new(Expr::Var(Symbol::Verb))))));
+        assert_eq!(Polynomial::from_expr(paths[0].result()).unwrap().to_string(), "noun + verb");
+    }
+
+    #[test]
+    fn forks_on_comparisons() {
+        // output(input0 < 8 ? 1 : 2) via JUMP_FALSE
+        let mut program = vec![3, 20, 1007, 20, 8, 21, 1006, 21, 14, 104, 1, 1105, 1, 16, 104, 2, 99];
+        program.resize(22, 0);
+        let paths = explore(consts(&program)).unwrap();
+        assert_eq!(paths.len(), 2);
+
+        let input = Expr::Var(Symbol::Input(0));
+        let condition = Expr::less_than(input, Expr::Const(8));
+        assert_eq!(paths[0].constraints, vec![Constraint { condition: condition.clone(), holds: true }]);
+        assert_eq!(paths[0].outputs, vec![Expr::Const(1)]);
+        assert_eq!(paths[1].constraints, vec![Constraint { condition, holds: false }]);
+        assert_eq!(paths[1].outputs, vec![Expr::Const(2)]);
+        assert_eq!(paths[1].constraints[0].to_string(), "[input0 < 8] == 0");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(explore_noun_verb(&[1, 0, 0, 0]).unwrap_err(), SymbolicError::OutOfRange { p: 4, address: 4 });
+        assert_eq!(explore_noun_verb(&[1, 0, 0, 4, 0, 99]).unwrap_err(), SymbolicError::SymbolicOpcode { p: 4 });
+        assert_eq!(explore_noun_verb(&[1105, 0, 0, 99]).unwrap_err(), SymbolicError::SymbolicJump { p: 0 });
+        assert_eq!(explore_noun_verb(&[42, 0, 0, 0]).unwrap_err(), SymbolicError::UnknownOpCode { p: 0, op_code: 42 });
+        assert_eq!(explore(consts(&[1105, 1, 0, 99])).unwrap_err(), SymbolicError::TooManySteps);
+    }
+
+    #[test]
+    fn polynomial_display() {
+        let noun = Polynomial::var(Symbol::Noun);
+        let p = noun.checked_mul(&Polynomial::constant(-3)).unwrap().checked_add(&Polynomial::constant(7)).unwrap();
+        assert_eq!(p.to_string(), "-3*noun + 7");
+        assert_eq!(p.coefficient(&[(Symbol::Noun, 1)]), -3);
+        assert_eq!(p.coefficient(&[]), 7);
+        assert_eq!(Polynomial::default().to_string(), "0");
+    }
+}
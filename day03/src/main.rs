@@ -3,6 +3,7 @@ use crate::wire::Wire;
 use std::str::FromStr;
 
 mod wire;
+mod svg;
 
 fn main() {
     let input = fs::read_to_string("input.txt")
@@ -11,11 +12,11 @@ fn main() {
         .lines()
         .collect();
 
-    let w1 : Wire = Wire::from_str(lines.get(0).unwrap()).unwrap();
-    let w2 : Wire = Wire::from_str(lines.get(1).unwrap()).unwrap();
+    let w1 : Wire = parse_wire(&lines, 0);
+    let w2 : Wire = parse_wire(&lines, 1);
 
-//    let overlap = w1.first_overlap(&w2);
-//    eprintln!("overlap = {:#?}", overlap);
+    let overlap = w1.first_overlap(&w2);
+    eprintln!("overlap = {:#?}", overlap);
 
     let intersections = w1.all_intersections(&w2);
     let result = intersections.iter()
@@ -25,5 +26,15 @@ fn main() {
 
     eprintln!("result = {:#?}", result);
 
+    if let Some(path) = std::env::args().nth(1) {
+        fs::write(&path, svg::render(&[w1, w2])).expect("Could not write svg");
+        eprintln!("wrote {}", path);
+    }
 
+
+}
+
+fn parse_wire(lines: &[&str], index: usize) -> Wire {
+    let line = lines.get(index).expect("Missing wire");
+    Wire::from_str(line).unwrap_or_else(|e| panic!("line {}, {}", index + 1, e))
 }
@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use crate::wire::{Wire, Point};
+
+const COLOURS: [&str; 6] = ["#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4", "#42d4f4"];
+const MARGIN: i32 = 10;
+
+/// A point where two or more wires cross, along with the number of steps
+/// each of those wires takes to reach it.
+#[derive(Debug, PartialEq)]
+pub struct Crossing {
+    pub point: Point,
+    pub steps: Vec<(usize, usize)>
+}
+
+impl Crossing {
+    pub fn total_steps(&self) -> usize {
+        self.steps.iter().map(|(_, steps)| steps).sum()
+    }
+}
+
+/// Every point shared by at least two of the wires, ordered by (x, y).
+pub fn crossings(wires: &[Wire]) -> Vec<Crossing> {
+    let mut crossed: BTreeMap<(i32, i32), Vec<usize>> = BTreeMap::new();
+    for (i, w1) in wires.iter().enumerate() {
+        for (j, w2) in wires.iter().enumerate().skip(i + 1) {
+            for p in w1.all_intersections(w2) {
+                let crossing_wires = crossed.entry((p.x, p.y)).or_default();
+                for w in [i, j].iter() {
+                    if !crossing_wires.contains(w) {
+                        crossing_wires.push(*w);
+                    }
+                }
+            }
+        }
+    }
+
+    crossed.into_iter()
+        .map(|((x, y), mut crossing_wires)| {
+            crossing_wires.sort();
+            let point = Point { x, y };
+            let steps = crossing_wires.iter()
+                .map(|&w| (w, wires[w].steps_to(&point)))
+                .collect();
+            Crossing { point, steps }
+        })
+        .collect()
+}
+
+/// Renders the wires as an SVG document. Each wire is drawn from the origin
+/// in its own colour, every crossing is marked and the Manhattan-closest and
+/// step-closest crossings are highlighted. Hovering a crossing shows its
+/// step counts.
+pub fn render(wires: &[Wire]) -> String {
+    let paths: Vec<Vec<Point>> = wires.iter().map(Wire::corners).collect();
+    let crossings = crossings(wires);
+
+    let all_points = || paths.iter().flatten();
+    let min_x = all_points().map(|p| p.x).min().unwrap_or(0) - MARGIN;
+    let max_x = all_points().map(|p| p.x).max().unwrap_or(0) + MARGIN;
+    let min_y = all_points().map(|p| p.y).min().unwrap_or(0) - MARGIN;
+    let max_y = all_points().map(|p| p.y).max().unwrap_or(0) + MARGIN;
+    let (width, height) = (max_x - min_x, max_y - min_y);
+    let radius = (width.max(height) / 200).max(1);
+
+    let closest = crossings.iter().min_by_key(|c| c.point.dist());
+    let fewest_steps = crossings.iter().min_by_key(|c| c.total_steps());
+
+    let mut svg = String::new();
+    writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#, min_x, min_y, width, height).unwrap();
+    writeln!(svg, r#"<rect x="{}" y="{}" width="{}" height="{}" fill="white"/>"#, min_x, min_y, width, height).unwrap();
+
+    for (i, path) in paths.iter().enumerate() {
+        let points = path.iter()
+            .map(|p| format!("{},{}", p.x, p.y))
+            .collect::<Vec<_>>()
+            .join(" ");
+        writeln!(svg, r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="1" vector-effect="non-scaling-stroke"><title>wire {}</title></polyline>"#,
+                 points, COLOURS[i % COLOURS.len()], i + 1).unwrap();
+    }
+
+    writeln!(svg, r#"<circle cx="0" cy="0" r="{}" fill="black"><title>origin</title></circle>"#, radius).unwrap();
+
+    for crossing in &crossings {
+        let is_closest = Some(crossing) == closest;
+        let is_fewest_steps = Some(crossing) == fewest_steps;
+        let (fill, r) = match (is_closest, is_fewest_steps) {
+            (true, true) => ("gold", radius * 3),
+            (true, false) => ("red", radius * 3),
+            (false, true) => ("blue", radius * 3),
+            (false, false) => ("none", radius)
+        };
+
+        let steps = crossing.steps.iter()
+            .map(|(w, steps)| format!("wire {}: {}", w + 1, steps))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut title = format!("({}, {}) distance {}, steps {} (total {})",
+                                crossing.point.x, crossing.point.y, crossing.point.dist(), steps, crossing.total_steps());
+        if is_closest { title.push_str(", closest"); }
+        if is_fewest_steps { title.push_str(", fewest steps"); }
+
+        writeln!(svg, r#"<circle cx="{}" cy="{}" r="{}" fill="{}" stroke="black" stroke-width="1" vector-effect="non-scaling-stroke"><title>{}</title></circle>"#,
+                 crossing.point.x, crossing.point.y, r, fill, title).unwrap();
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Vec<Wire> {
+        vec!["R8,U5,L5,D3".parse().unwrap(), "U7,R6,D4,L4".parse().unwrap()]
+    }
+
+    #[test]
+    fn crossings_with_steps() {
+        let crossings = crossings(&example());
+        assert_eq!(crossings, vec![
+            Crossing { point: Point { x: 3, y: -3 }, steps: vec![(0, 20), (1, 20)] },
+            Crossing { point: Point { x: 6, y: -5 }, steps: vec![(0, 15), (1, 15)] },
+        ]);
+    }
+
+    #[test]
+    fn render_marks_wires_and_crossings() {
+        let svg = render(&example());
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(r#"points="0,0 8,0 8,-5 3,-5 3,-2""#));
+        assert!(svg.contains(r#"points="0,0 0,-7 6,-7 6,-3 2,-3""#));
+        assert!(svg.contains("<title>(3, -3) distance 6, steps wire 1: 20, wire 2: 20 (total 40), closest</title>"));
+        assert!(svg.contains("<title>(6, -5) distance 11, steps wire 1: 15, wire 2: 15 (total 30), fewest steps</title>"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+}
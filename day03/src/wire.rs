@@ -1,16 +1,14 @@
-use std::fs;
 use std::str::FromStr;
-use nom::sequence::{tuple, pair};
+use std::fmt::{Display, Formatter};
+use nom::sequence::{pair, delimited, terminated, preceded};
 use nom::multi::separated_list;
-use nom::combinator::{map, map_res};
+use nom::combinator::{map, map_res, cut, all_consuming};
 use nom::IResult;
-use nom::character::complete::{alpha1, digit1};
-use nom::bytes::complete::tag;
+use nom::error::ErrorKind;
+use nom::character::complete::{char, digit1, one_of, space0, multispace0};
 use crate::wire::Direction::{Down, Up, Right, Left};
-use std::cmp::{min, max};
 use num::range_step;
 use std::error::Error;
-use std::num::ParseIntError;
 
 #[derive(Debug)]
 pub struct Wire {
@@ -20,8 +18,8 @@ pub struct Wire {
 
 #[derive(Clone,PartialEq,Debug)]
 pub struct Point {
-    x: i32,
-    y: i32
+    pub x: i32,
+    pub y: i32
 }
 
 impl Point {
@@ -51,7 +49,7 @@ impl Point {
         }
     }
     
-    fn dist(&self) -> i32 {
+    pub fn dist(&self) -> i32 {
         self.y.abs() + self.x.abs()
     }
 }
@@ -63,17 +61,31 @@ impl Wire {
         Wire { instructions, positions }
     }
 
-    fn calc_positions(instructions: &Vec<Instruction>) -> Vec<Point> {
+    fn calc_positions(instructions: &[Instruction]) -> Vec<Point> {
         let mut p = Point { x:0, y: 0 };
         instructions.iter()
             .flat_map(|i| {
                 let new_position = p.move_by(i);
-                let points = p.points_between(&i);
+                let points = p.points_between(i);
                 p = new_position;
                 points
             }).collect()
     }
     
+    /// The origin followed by the end point of every instruction, i.e. the
+    /// vertices of the path the wire takes.
+    pub fn corners(&self) -> Vec<Point> {
+        let origin = Point { x: 0, y: 0 };
+        let mut corners = vec![origin.clone()];
+        self.instructions.iter()
+            .fold(origin, |p, i| {
+                let next = p.move_by(i);
+                corners.push(next.clone());
+                next
+            });
+        corners
+    }
+
     pub fn first_overlap(&self, other: &Wire) -> i32 {
         self.positions.iter().skip(1)
             .filter(|p| other.positions.contains(p))
@@ -108,34 +120,115 @@ enum Direction {
     Down
 }
 
-impl FromStr for Instruction {
-    type Err = ();
+/// Where and why a wire path failed to parse. `column` is 1-based and counts
+/// characters from the start of the string handed to the parser.
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub column: usize,
+    pub kind: ParseErrorKind
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (direction, distance) = s.split_at(1);
-        let direction = match direction {
-            "U" => Ok(Up),
-            "D" => Ok(Down),
-            "L" => Ok(Left),
-            "R" => Ok(Right),
-            _ => Err(())
-        }?;
+#[derive(Debug, PartialEq)]
+pub enum ParseErrorKind {
+    BadDirection(Option<char>),
+    MissingDistance,
+    DistanceOverflow,
+    TrailingInput
+}
 
-        let distance = distance.parse().map_err(|_| ())?;
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            ParseErrorKind::BadDirection(Some(c)) => write!(f, "column {}: expected one of U, D, L, R but found '{}'", self.column, c),
+            ParseErrorKind::BadDirection(None) => write!(f, "column {}: expected one of U, D, L, R but found end of input", self.column),
+            ParseErrorKind::MissingDistance => write!(f, "column {}: expected a distance", self.column),
+            ParseErrorKind::DistanceOverflow => write!(f, "column {}: distance is too large", self.column),
+            ParseErrorKind::TrailingInput => write!(f, "column {}: unexpected trailing input", self.column)
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// nom error that just remembers the innermost failure, which is the one
+/// worth reporting back to the user.
+#[derive(Debug)]
+struct WireError<'a> {
+    input: &'a str,
+    kind: ErrorKind
+}
+
+impl<'a> nom::error::ParseError<&'a str> for WireError<'a> {
+    fn from_error_kind(input: &'a str, kind: ErrorKind) -> Self {
+        WireError { input, kind }
+    }
 
-        Ok( Instruction { direction, distance })
+    fn append(_input: &'a str, _kind: ErrorKind, other: Self) -> Self {
+        other
     }
 }
 
-impl FromStr for Wire {
-    type Err = ();
+impl<'a> WireError<'a> {
+    fn into_parse_error(self, full: &str) -> ParseError {
+        let offset = full.len() - self.input.len();
+        let column = full[..offset].chars().count() + 1;
+        let kind = match self.kind {
+            ErrorKind::OneOf => ParseErrorKind::BadDirection(self.input.chars().next()),
+            ErrorKind::Digit => ParseErrorKind::MissingDistance,
+            ErrorKind::MapRes => ParseErrorKind::DistanceOverflow,
+            _ => ParseErrorKind::TrailingInput
+        };
+        ParseError { column, kind }
+    }
+}
+
+type ParseResult<'a, T> = IResult<&'a str, T, WireError<'a>>;
+
+fn direction(input: &str) -> ParseResult<'_, Direction> {
+    map(one_of("UDLR"), |c| match c {
+        'U' => Up,
+        'D' => Down,
+        'L' => Left,
+        _ => Right
+    })(input)
+}
+
+fn distance(input: &str) -> ParseResult<'_, i32> {
+    map_res(digit1, str::parse)(input)
+}
+
+fn instruction(input: &str) -> ParseResult<'_, Instruction> {
+    map(pair(direction, cut(distance)),
+        |(direction, distance)| Instruction { direction, distance })(input)
+}
+
+fn instructions(input: &str) -> ParseResult<'_, Vec<Instruction>> {
+    let separator = delimited(space0, char(','), space0);
+    delimited(multispace0, separated_list(separator, cut(instruction)), multispace0)(input)
+}
+
+fn parse_all<'a, T, P>(parser: P, s: &'a str) -> Result<T, ParseError>
+    where P: Fn(&'a str) -> ParseResult<'a, T> {
+    match all_consuming(parser)(s) {
+        Ok((_, result)) => Ok(result),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => Err(e.into_parse_error(s)),
+        Err(nom::Err::Incomplete(_)) => unreachable!("complete parsers never ask for more input")
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let instructions  = s.split(",")
-            .filter_map(|s| s.parse().ok())
-            .collect();
+        parse_all(terminated(preceded(space0, instruction), space0), s)
+    }
+}
+
+impl FromStr for Wire {
+    type Err = ParseError;
 
-        Ok(Wire::new(instructions))
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_all(instructions, s).map(Wire::new)
     }
 }
 
@@ -147,8 +240,8 @@ mod tests {
     fn parse2() {
         let w : Wire = "D99,L45".parse().unwrap();
         assert_eq!(w.instructions.len(), 2);
-        assert_eq!(w.instructions.get(0).unwrap().direction, Down);
-        assert_eq!(w.instructions.get(0).unwrap().distance, 99);
+        assert_eq!(w.instructions.first().unwrap().direction, Down);
+        assert_eq!(w.instructions.first().unwrap().distance, 99);
     }
     
     #[test]
@@ -223,7 +316,6 @@ mod tests {
     fn steps_to2() {
         let w1 : Wire = Wire::from_str("R75,D30,R83,U83,L12,D49,R71,U7,L72").unwrap();
         let w2 : Wire = Wire::from_str("U62,R66,U55,R34,D71,R55,D58,R83").unwrap();
-        let point = Point { x: 3, y: -3 };
 
         let intersections = w1.all_intersections(&w2);
         let result = intersections.iter()
@@ -232,9 +324,69 @@ mod tests {
             .expect("Should be min");
         assert_eq!(result, 610);
     }
-}
 
+    #[test]
+    fn corners() {
+        let w : Wire = "R8,U5,L5,D3".parse().unwrap();
+        assert_eq!(w.corners(), vec![
+            Point { x: 0, y: 0 },
+            Point { x: 8, y: 0 },
+            Point { x: 8, y: -5 },
+            Point { x: 3, y: -5 },
+            Point { x: 3, y: -2 },
+        ]);
+    }
+
+    #[test]
+    fn parse_whitespace() {
+        let w : Wire = " R8 , U5,L5 ,D3\n\n".parse().unwrap();
+        assert_eq!(w.instructions.len(), 4);
+        assert_eq!(w.instructions[3].direction, Down);
+        assert_eq!(w.instructions[3].distance, 3);
+    }
+
+    #[test]
+    fn parse_bad_direction() {
+        let err = "R8,X5".parse::<Wire>().unwrap_err();
+        assert_eq!(err, ParseError { column: 4, kind: ParseErrorKind::BadDirection(Some('X')) });
+
+        let err = "R8,".parse::<Wire>().unwrap_err();
+        assert_eq!(err, ParseError { column: 4, kind: ParseErrorKind::BadDirection(None) });
+
+        let err = "R8,Ü5".parse::<Wire>().unwrap_err();
+        assert_eq!(err, ParseError { column: 4, kind: ParseErrorKind::BadDirection(Some('Ü')) });
+    }
 
+    #[test]
+    fn column_counts_characters() {
+        let full = "ÜÜR8";
+        let err = WireError { input: &full[4..], kind: ErrorKind::Eof }.into_parse_error(full);
+        assert_eq!(err.column, 3);
+    }
+
+    #[test]
+    fn parse_missing_distance() {
+        let err = "R8,U,L5".parse::<Wire>().unwrap_err();
+        assert_eq!(err, ParseError { column: 5, kind: ParseErrorKind::MissingDistance });
+    }
 
+    #[test]
+    fn parse_overflow() {
+        let err = "R8,U99999999999".parse::<Wire>().unwrap_err();
+        assert_eq!(err, ParseError { column: 5, kind: ParseErrorKind::DistanceOverflow });
+    }
 
+    #[test]
+    fn parse_trailing_input() {
+        let err = "R8,U5 L5".parse::<Wire>().unwrap_err();
+        assert_eq!(err, ParseError { column: 7, kind: ParseErrorKind::TrailingInput });
+    }
 
+    #[test]
+    fn parse_instruction() {
+        let i : Instruction = "L45".parse().unwrap();
+        assert_eq!(i.direction, Left);
+        assert_eq!(i.distance, 45);
+        assert!("L45,".parse::<Instruction>().is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use crate::rules::{Policy, Rule, digits, digit_bounds};
+
+/// How `Policy` passwords get counted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Counting {
+    /// Check every candidate in the search space.
+    BruteForce,
+    /// Digit DP over run lengths, which never looks at individual passwords.
+    Combinatorial
+}
+
+/// One bit each in `State::satisfied`.
+const MAX_RUN_RULES : usize = 32;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct State {
+    tight_low: bool,
+    tight_high: bool,
+    last: Option<u8>,
+    run: usize,
+    satisfied: u32
+}
+
+/// The run rules of a policy, each owning one bit of `State::satisfied`.
+struct RunRules {
+    rules: Vec<Rule>,
+    cap: usize
+}
+
+impl RunRules {
+    fn new(policy: &Policy) -> RunRules {
+        let rules: Vec<Rule> = policy.rules().iter()
+            .filter(|rule| matches!(rule, Rule::RunOfExactly(_) | Rule::RunOfAtLeast(_)))
+            .cloned()
+            .collect();
+        // A run only needs tracking until it is longer than any rule cares about
+        let cap = rules.iter()
+            .map(|rule| match rule { Rule::RunOfExactly(len) | Rule::RunOfAtLeast(len) => *len, _ => 0 })
+            .max()
+            .unwrap_or(0) + 1;
+        RunRules { rules, cap }
+    }
+
+    fn all(&self) -> u32 {
+        ((1u64 << self.rules.len()) - 1) as u32
+    }
+
+    fn close_run(&self, satisfied: u32, run: usize) -> u32 {
+        self.rules.iter().enumerate()
+            .filter(|(_, rule)| match rule {
+                Rule::RunOfExactly(len) => run == *len,
+                Rule::RunOfAtLeast(len) => run >= *len,
+                _ => false
+            })
+            .fold(satisfied, |acc, (i, _)| acc | 1 << i)
+    }
+}
+
+/// Counts matching passwords without enumerating them. The search space is
+/// split by digit count and each part is counted with a digit DP whose state
+/// is the previous digit, the length of the current run and which run rules
+/// have already been met.
+pub fn count_combinatorial(policy: &Policy) -> Result<u64, String> {
+    let space = policy.search_space().ok_or("The rules need a range or a digit count")?;
+    let (low, high) = (*space.start(), *space.end());
+    if low > high {
+        return Ok(0);
+    }
+
+    let run_rules = RunRules::new(policy);
+    if run_rules.rules.len() > MAX_RUN_RULES {
+        return Err(format!("Can count with at most {} run rules, but found {}", MAX_RUN_RULES, run_rules.rules.len()));
+    }
+    let non_decreasing = policy.rules().contains(&Rule::NonDecreasing);
+
+    let total = (digits(low).len()..=digits(high).len())
+        .map(|len| {
+            let (min, max) = digit_bounds(len);
+            count_between(&digits(low.max(min)), &digits(high.min(max)), non_decreasing, &run_rules)
+        })
+        .sum();
+
+    Ok(total)
+}
+
+/// Counts the passwords between `low` and `high` inclusive, which must have
+/// the same number of digits.
+fn count_between(low: &[u8], high: &[u8], non_decreasing: bool, run_rules: &RunRules) -> u64 {
+    let start = State { tight_low: true, tight_high: true, last: None, run: 0, satisfied: 0 };
+    let mut states: HashMap<State, u64> = HashMap::new();
+    states.insert(start, 1);
+
+    for (i, (&low_digit, &high_digit)) in low.iter().zip(high.iter()).enumerate() {
+        let mut next: HashMap<State, u64> = HashMap::new();
+        for (state, count) in states {
+            let from = if state.tight_low { low_digit } else { 0 };
+            let to = if state.tight_high { high_digit } else { 9 };
+            for digit in from..=to {
+                if non_decreasing && state.last.is_some_and(|last| digit < last) {
+                    continue;
+                }
+                let (run, satisfied) = if state.last == Some(digit) {
+                    ((state.run + 1).min(run_rules.cap), state.satisfied)
+                } else if i == 0 {
+                    (1, state.satisfied)
+                } else {
+                    (1, run_rules.close_run(state.satisfied, state.run))
+                };
+                let next_state = State {
+                    tight_low: state.tight_low && digit == low_digit,
+                    tight_high: state.tight_high && digit == high_digit,
+                    last: Some(digit),
+                    run,
+                    satisfied
+                };
+                *next.entry(next_state).or_insert(0) += count;
+            }
+        }
+        states = next;
+    }
+
+    states.into_iter()
+        .filter(|(state, _)| run_rules.close_run(state.satisfied, state.run) == run_rules.all())
+        .map(|(_, count)| count)
+        .sum()
+}
+
+impl Policy {
+    pub fn count_with(&self, counting: Counting) -> Result<u64, String> {
+        match counting {
+            Counting::BruteForce => self.count(),
+            Counting::Combinatorial => count_combinatorial(self)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cross_check(policy: Policy) {
+        assert_eq!(policy.count_with(Counting::Combinatorial), policy.count_with(Counting::BruteForce), "{}", policy);
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        cross_check("non-decreasing run>=2 273025-767253".parse().unwrap());
+        cross_check("non-decreasing run=2 273025-767253".parse().unwrap());
+        cross_check("run=2 digits=4".parse().unwrap());
+        cross_check("run=3 run>=2 0-99999".parse().unwrap());
+        cross_check("non-decreasing run=1 run=3 7-123456".parse().unwrap());
+        cross_check("digits=3 500-400".parse().unwrap());
+        cross_check("0-0".parse().unwrap());
+    }
+
+    #[test]
+    fn many_digits() {
+        let policy: Policy = "non-decreasing run=2 digits=19".parse().unwrap();
+        let count = policy.count_with(Counting::Combinatorial).unwrap();
+        let wide: Policy = "run>=3 1-18446744073709551615".parse().unwrap();
+        assert_eq!(count, 1734057);
+        assert_eq!(wide.count_with(Counting::Combinatorial).unwrap(), 2751943853484595817);
+    }
+
+    #[test]
+    fn too_many_run_rules() {
+        let rules = |n| format!("{}digits=3", "run>=1 ".repeat(n));
+        let most: Policy = rules(MAX_RUN_RULES).parse().unwrap();
+        assert_eq!(most.count_with(Counting::Combinatorial).unwrap(), 900);
+        let too_many: Policy = rules(MAX_RUN_RULES + 1).parse().unwrap();
+        assert_eq!(too_many.count_with(Counting::Combinatorial), Err("Can count with at most 32 run rules, but found 33".to_string()));
+    }
+}
@@ -1,90 +1,130 @@
-use itertools::Itertools;
+use std::env;
+use std::fs;
+use crate::rules::{Policy, Rule};
+use crate::count::Counting;
 
-fn main() {
-    let part1 = solve(meets_criteria01);
-    eprintln!("part1 = {:#?}", part1);
+pub mod rules;
+pub mod count;
 
-    let part2 = solve(meets_criteria02);
-    eprintln!("part2 = {:#?}", part2);
-}
+const PUZZLE_RANGE: (u64, u64) = (273025, 767253);
 
-fn solve<C>(criteria: C) -> usize 
-    where C : Fn(&str) -> bool {
-    (273025..767253)
-        .filter(|password| criteria(&password.to_string()))
-        .count()
-}
+/// With no arguments, solves both parts for the puzzle range, cross-checking
+/// the combinatorial count against brute force. Otherwise the arguments are
+/// rules (see `Rule::from_str`), or `--config <file>` to read them from a
+/// file, and the number of matching passwords is printed. Passing
+/// `--brute-force` first checks every candidate instead.
+fn main() -> Result<(), String> {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    if args.is_empty() {
+        let part1 = cross_checked(&part1_policy())?;
+        eprintln!("part1 = {:#?}", part1);
 
+        let part2 = cross_checked(&part2_policy())?;
+        eprintln!("part2 = {:#?}", part2);
+    } else {
+        let counting = if args[0] == "--brute-force" {
+            args.remove(0);
+            Counting::BruteForce
+        } else {
+            Counting::Combinatorial
+        };
+        let policy = policy_from_args(&args)?;
+        println!("{} => {}", policy, policy.count_with(counting)?);
+    }
 
-fn meets_criteria01(password: &str) -> bool {
-    is_not_decreasing(password) && contains_double(password)
+    Ok(())
 }
 
-fn meets_criteria02(password: &str) -> bool {
-    is_not_decreasing(password) && contains_double_no_surrounding(password)
+fn cross_checked(policy: &Policy) -> Result<u64, String> {
+    let brute_force = policy.count_with(Counting::BruteForce)?;
+    let combinatorial = policy.count_with(Counting::Combinatorial)?;
+    if brute_force == combinatorial {
+        Ok(combinatorial)
+    } else {
+        Err(format!("{}: brute force counted {} but combinatorial counted {}", policy, brute_force, combinatorial))
+    }
 }
 
-fn is_not_decreasing(password: &str) -> bool {
-    password.chars()
-        .filter_map(|c| c.to_digit(10))
-        .tuple_windows()
-        .fold(true,|acc, (prev,next)| acc && next >= prev)
+fn policy_from_args(args: &[String]) -> Result<Policy, String> {
+    let first = args.first().ok_or("no policy given")?;
+    if first == "--config" {
+        let file = args.get(1).ok_or("--config needs a file")?;
+        fs::read_to_string(file)
+            .map_err(|e| format!("Couldn't read {}: {}", file, e))?
+            .parse()
+    } else {
+        args.join(" ").parse()
+    }
 }
 
-fn contains_double(password: &str) -> bool {
-    password.chars()
-        .tuple_windows()
-        .any(|(a,b)| a == b)
+fn puzzle_policy() -> Policy {
+    let (low, high) = PUZZLE_RANGE;
+    Policy::new(vec![Rule::Digits(6), Rule::Within(low, high), Rule::NonDecreasing])
 }
 
-fn contains_double_no_surrounding(password: &str) -> bool {
-    let doubles_inside = password.chars()
-        .tuple_windows::<(_,_,_,_)>()
-        .fold(false, |acc, (a, b, c ,d)| {
-            acc || b == c && a != b && c != d
-        });
-
-    let start : Vec<char>= password.chars().take(3).collect();
-    let (a, b, c) = (&start[0], &start[1], &start[2]);
-    let starting_double = a == b && b != c;
-    
-    let end : Vec<char>= password.chars().rev().take(3).collect();
-    let (a, b, c) = (&end[0], &end[1], &end[2]);
-    let ending_double = a == b && b != c;
-
-    doubles_inside || starting_double || ending_double
+fn part1_policy() -> Policy {
+    puzzle_policy().with(Rule::RunOfAtLeast(2))
 }
 
+fn part2_policy() -> Policy {
+    puzzle_policy().with(Rule::RunOfExactly(2))
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
+    fn meets(rules: Vec<Rule>, password: u64) -> bool {
+        Policy::new(rules).matches(password)
+    }
+
     #[test]
     fn test_not_dec() {
-        assert_eq!(is_not_decreasing("123456"), true);
-        assert_eq!(is_not_decreasing("123455"), true);
-        assert_eq!(is_not_decreasing("123454"), false);
+        assert!(meets(vec![Rule::NonDecreasing], 123456));
+        assert!(meets(vec![Rule::NonDecreasing], 123455));
+        assert!(!meets(vec![Rule::NonDecreasing], 123454));
     }
-    
+
     #[test]
     fn doubles() {
-        assert_eq!(contains_double("asdf"), false);
-        assert_eq!(contains_double("asdff"), true);
+        assert!(!meets(vec![Rule::RunOfAtLeast(2)], 1234));
+        assert!(meets(vec![Rule::RunOfAtLeast(2)], 12344));
     }
-    
+
     #[test]
     fn examples() {
-        assert_eq!(meets_criteria01("111111"), true);
-        assert_eq!(meets_criteria01("223450"), false);
-        assert_eq!(meets_criteria01("123789"), false);
+        let part1 = Policy::new(vec![Rule::NonDecreasing, Rule::RunOfAtLeast(2)]);
+        assert!(part1.matches(111111));
+        assert!(!part1.matches(223450));
+        assert!(!part1.matches(123789));
     }
-    
+
     #[test]
     fn doubles_no_surrounding() {
-        assert_eq!(contains_double_no_surrounding("111221"), true);
-        assert_eq!(contains_double_no_surrounding("1112221"), false);
-        assert_eq!(contains_double_no_surrounding("112221"), true);
-        assert_eq!(contains_double_no_surrounding("122211"), true);
+        assert!(meets(vec![Rule::RunOfExactly(2)], 111221));
+        assert!(!meets(vec![Rule::RunOfExactly(2)], 1112221));
+        assert!(meets(vec![Rule::RunOfExactly(2)], 112221));
+        assert!(meets(vec![Rule::RunOfExactly(2)], 122211));
+    }
+
+    #[test]
+    fn puzzle() {
+        assert_eq!(part1_policy().count(), Ok(910));
+        assert_eq!(part2_policy().count(), Ok(598));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn puzzle_cross_checked() {
+        assert_eq!(cross_checked(&part1_policy()), Ok(910));
+        assert_eq!(cross_checked(&part2_policy()), Ok(598));
+    }
+
+    #[test]
+    fn args() {
+        let args = vec!["non-decreasing".to_string(), "run=2".to_string(), "100-200".to_string()];
+        let policy = policy_from_args(&args).unwrap();
+        assert_eq!(policy.rules(), &[Rule::NonDecreasing, Rule::RunOfExactly(2), Rule::Within(100, 200)]);
+        assert_eq!(policy_from_args(&[]).err(), Some("no policy given".to_string()));
+    }
+}
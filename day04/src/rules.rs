@@ -0,0 +1,232 @@
+use std::fmt::{Display, Formatter, Error};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use itertools::Itertools;
+
+/// A single constraint on a password.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rule {
+    /// Going from left to right, the digits never decrease.
+    NonDecreasing,
+    /// Some run of adjacent equal digits is exactly this long.
+    RunOfExactly(usize),
+    /// Some run of adjacent equal digits is at least this long.
+    RunOfAtLeast(usize),
+    /// The password has exactly this many digits.
+    Digits(usize),
+    /// The password lies within this inclusive range.
+    Within(u64, u64)
+}
+
+/// The largest digit count whose passwords all fit in a `u64`.
+pub const MAX_DIGITS: usize = 19;
+
+impl Rule {
+    fn check(&self, password: u64, digits: &[u8]) -> bool {
+        match *self {
+            Rule::NonDecreasing => digits.iter().tuple_windows().all(|(a, b)| a <= b),
+            Rule::RunOfExactly(len) => runs(digits).any(|run| run == len),
+            Rule::RunOfAtLeast(len) => runs(digits).any(|run| run >= len),
+            Rule::Digits(len) => digits.len() == len,
+            Rule::Within(low, high) => low <= password && password <= high
+        }
+    }
+}
+
+/// The lengths of each run of adjacent equal digits, left to right.
+pub fn runs(digits: &[u8]) -> impl Iterator<Item=usize> + '_ {
+    let mut rest = digits;
+    std::iter::from_fn(move || {
+        let first = *rest.first()?;
+        let len = rest.iter().take_while(|&&d| d == first).count();
+        rest = &rest[len..];
+        Some(len)
+    })
+}
+
+pub fn digits(password: u64) -> Vec<u8> {
+    password.to_string().bytes().map(|b| b - b'0').collect()
+}
+
+/// A set of rules that must all hold.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Policy {
+    rules: Vec<Rule>
+}
+
+impl Policy {
+    pub fn new(rules: Vec<Rule>) -> Policy {
+        Policy { rules }
+    }
+
+    pub fn with(mut self, rule: Rule) -> Policy {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn matches(&self, password: u64) -> bool {
+        let digits = digits(password);
+        self.rules.iter().all(|rule| rule.check(password, &digits))
+    }
+
+    /// The passwords that the range and digit-count rules allow, or `None`
+    /// if the rules don't bound the search at all.
+    pub fn search_space(&self) -> Option<RangeInclusive<u64>> {
+        let mut bounded = false;
+        let (mut low, mut high) = (0, u64::MAX);
+        for rule in &self.rules {
+            match *rule {
+                Rule::Within(l, h) => {
+                    low = low.max(l);
+                    high = high.min(h);
+                    bounded = true;
+                },
+                Rule::Digits(len) => {
+                    let (l, h) = digit_bounds(len);
+                    low = low.max(l);
+                    high = high.min(h);
+                    bounded = true;
+                },
+                _ => {}
+            }
+        }
+        if bounded { Some(low..=high) } else { None }
+    }
+
+    /// Counts matching passwords by checking every candidate in the search space.
+    pub fn count(&self) -> Result<u64, String> {
+        let space = self.search_space().ok_or("The rules need a range or a digit count")?;
+        Ok(space.filter(|&password| self.matches(password)).count() as u64)
+    }
+}
+
+/// The smallest and largest passwords with exactly `len` digits that fit in
+/// a `u64`, or an empty range if there are none.
+pub fn digit_bounds(len: usize) -> (u64, u64) {
+    match len {
+        0 => (1, 0),
+        1 => (0, 9),
+        _ => match 10u64.checked_pow(len as u32 - 1) {
+            Some(low) => (low, low.checked_mul(10).map_or(u64::MAX, |high| high - 1)),
+            None => (1, 0)
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    /// Accepts `non-decreasing`, `run=N`, `run>=N`, `digits=N` and `LOW-HIGH`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let number = |n: &str| n.trim().parse::<u64>().map_err(|_| format!("Bad number '{}' in rule '{}'", n, s));
+
+        if s == "non-decreasing" {
+            Ok(Rule::NonDecreasing)
+        } else if let Some(n) = s.strip_prefix("run>=") {
+            Ok(Rule::RunOfAtLeast(number(n)? as usize))
+        } else if let Some(n) = s.strip_prefix("run=") {
+            Ok(Rule::RunOfExactly(number(n)? as usize))
+        } else if let Some(n) = s.strip_prefix("digits=") {
+            let len = number(n)? as usize;
+            if len > MAX_DIGITS {
+                return Err(format!("At most {} digits are supported", MAX_DIGITS));
+            }
+            Ok(Rule::Digits(len))
+        } else if let Some((low, high)) = s.splitn(2, '-').collect_tuple() {
+            Ok(Rule::Within(number(low)?, number(high)?))
+        } else {
+            Err(format!("Unknown rule '{}'", s))
+        }
+    }
+}
+
+impl FromStr for Policy {
+    type Err = String;
+
+    /// Rules separated by whitespace. Anything after a `#` on a line is ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rules = s.lines()
+            .map(|line| line.split('#').next().unwrap_or(""))
+            .flat_map(|line| line.split_whitespace())
+            .map(str::parse)
+            .collect::<Result<Vec<Rule>, String>>()?;
+        Ok(Policy::new(rules))
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            Rule::NonDecreasing => write!(f, "non-decreasing"),
+            Rule::RunOfExactly(len) => write!(f, "run={}", len),
+            Rule::RunOfAtLeast(len) => write!(f, "run>={}", len),
+            Rule::Digits(len) => write!(f, "digits={}", len),
+            Rule::Within(low, high) => write!(f, "{}-{}", low, high)
+        }
+    }
+}
+
+impl Display for Policy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}", self.rules.iter().join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_lengths() {
+        assert_eq!(runs(&digits(111122)).collect::<Vec<_>>(), vec![4, 2]);
+        assert_eq!(runs(&digits(123444)).collect::<Vec<_>>(), vec![1, 1, 1, 3]);
+        assert_eq!(runs(&[]).count(), 0);
+    }
+
+    #[test]
+    fn rules() {
+        let policy = Policy::new(vec![Rule::RunOfAtLeast(3)]);
+        assert!(policy.matches(1112));
+        assert!(!policy.matches(1122));
+
+        let policy = Policy::new(vec![Rule::Digits(4), Rule::Within(1000, 1500)]);
+        assert!(policy.matches(1234));
+        assert!(!policy.matches(1501));
+        assert!(!policy.matches(999));
+    }
+
+    #[test]
+    fn search_space() {
+        assert_eq!(Policy::default().search_space(), None);
+        let policy = Policy::new(vec![Rule::Digits(3), Rule::Within(50, 150)]);
+        assert_eq!(policy.search_space(), Some(100..=150));
+        assert_eq!(Policy::new(vec![Rule::Digits(19)]).search_space(), Some(1_000_000_000_000_000_000..=9_999_999_999_999_999_999));
+    }
+
+    #[test]
+    fn parse() {
+        let policy: Policy = "non-decreasing run=2 # part 2\n273025-767253 digits=6".parse().unwrap();
+        assert_eq!(policy.rules(), &[
+            Rule::NonDecreasing,
+            Rule::RunOfExactly(2),
+            Rule::Within(273025, 767253),
+            Rule::Digits(6)
+        ]);
+        assert_eq!(policy.to_string(), "non-decreasing run=2 273025-767253 digits=6");
+        assert!("run=x".parse::<Rule>().is_err());
+        assert!("digits=20".parse::<Rule>().is_err());
+        assert!("sorted".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn count_any_digit_count() {
+        let policy = Policy::new(vec![Rule::NonDecreasing, Rule::Digits(2)]);
+        assert_eq!(policy.count(), Ok(45));
+        assert!(Policy::new(vec![Rule::NonDecreasing]).count().is_err());
+    }
+}
@@ -1,169 +1,118 @@
 use std::fs;
 use std::error::Error;
-use crate::ParameterMode::{Immediate, Position};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use intcode::{Program, Step};
+
+const AIR_CONDITIONER : i64 = 1;
+const THERMAL_RADIATOR : i64 = 5;
 
 fn main() {
-    let input : Vec<i32> = fs::read_to_string("input.txt")
+    let input : Vec<i64> = fs::read_to_string("input.txt")
         .expect("Something went wrong reading the file")
-        .split(",")
-        .map(|x| x.parse::<i32>().unwrap())
+        .trim()
+        .split(',')
+        .map(|x| x.parse::<i64>().unwrap())
         .collect();
 
-    let mut p = Program::new(input, 5);
-    p.exec();
-        
-}
-
-type OpCodes = Vec<i32>;
+    let part1 = diagnostic(&input, AIR_CONDITIONER).expect("Air conditioner diagnostic failed");
+    eprintln!("part1 = {:#?}", part1);
 
-struct Program {
-    op_codes : OpCodes,
-    p : usize,
-    input: i32
+    let part2 = diagnostic(&input, THERMAL_RADIATOR).expect("Thermal radiator diagnostic failed");
+    eprintln!("part2 = {:#?}", part2);
 }
 
-#[derive(PartialEq, Debug)]
-enum ParameterMode {
-    Position,
-    Immediate
+/// Runs the program, feeding it `inputs` in order, and returns everything it
+/// outputs. Asking for more input than that is an error.
+fn run(op_codes: &[i64], inputs: Vec<i64>) -> Result<Vec<i64>, Box<dyn Error>> {
+    let mut p : Program<()> = Program::new(&op_codes.to_vec());
+
+    let starved = Rc::new(Cell::new(false));
+    let inputs = RefCell::new(inputs.into_iter());
+    let ran_out = starved.clone();
+    p.set_input_fn(move |_| {
+        inputs.borrow_mut().next().unwrap_or_else(|| { ran_out.set(true); 0 })
+    });
+
+    let mut outputs = vec![];
+    loop {
+        match p.step()? {
+            Step::Input(_) if starved.get() => return Err("Program asked for more input than it was given".into()),
+            Step::Output(output) => outputs.push(output),
+            Step::Halted => return Ok(outputs),
+            _ => {}
+        }
+    }
 }
 
-const HALT : i32 = 99;
-const ADD : i32 = 1;
-const MUL : i32 = 2;
-const INPUT : i32 = 3;
-const OUTPUT : i32 = 4;
-const JUMP_TRUE : i32 = 5;
-const JUMP_FALSE : i32 = 6;
-const LESS_THAN : i32 = 7;
-const EQUALS : i32 = 8;
+/// Runs the TEST diagnostic for a system. Every output but the last reports
+/// how far a check was from passing, so must be zero; the last output is the
+/// diagnostic code.
+fn diagnostic(op_codes: &[i64], system_id: i64) -> Result<i64, Box<dyn Error>> {
+    let outputs = run(op_codes, vec![system_id])?;
+    let (code, checks) = outputs.split_last().ok_or("Diagnostic produced no output")?;
 
-
-impl Program {
-    fn new(op_codes: OpCodes, input: i32) -> Program {
-        Program { p: 0, input, op_codes}
+    if let Some((i, failure)) = checks.iter().enumerate().find(|(_, &check)| check != 0) {
+        return Err(format!("Check {} failed with {}", i + 1, failure).into());
     }
-    
-    fn exec(&mut self) -> Result<i32, Box<dyn Error>> {
-        while self.next_code() != HALT {
-            
-            let code = self.next_code();
-            match code {
-                ADD   => self.add(),
-                MUL   => self.mul(),
-                INPUT => self.input(),
-                OUTPUT => self.output(),
-                JUMP_TRUE => self.jump_if_true(),
-                JUMP_FALSE => self.jump_if_false(),
-                LESS_THAN => self.less_than(),
-                EQUALS => self.equals(),
-                HALT  => break,
-                _ => return Err(format!("Unknown OpCode").into())
-            }   
-        }
 
-        Ok(0)
-    }
-    
-    fn input(&mut self) {
-        let index = self.op_codes[self.p + 1] as usize;
-        self.op_codes[index] = self.input;
-        self.p+=2
-    }
-    
-    fn output(&mut self) {
-        println!("{}", self.op_codes[self.op_codes[self.p +1] as usize]);
-        self.p+=2
-    }
-    
-    fn jump_if_true(&mut self) {
-        if self.param1() !=0 {
-            self.p = self.param2() as usize;
-        } else {
-            self.p +=3;
-        }
-    }
+    Ok(*code)
+}
 
-    fn jump_if_false(&mut self) {
-        if self.param1() == 0 {
-            self.p = self.param2() as usize;
-        } else {
-            self.p += 3;
-        }
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn equals(&mut self) {
-        self.binary_op(|a, b| if a == b { 1 } else { 0 });
-    }
-    
-    fn less_than(&mut self) {
-        self.binary_op(|a, b| if a < b { 1 } else { 0 });
-    }
-    
-    fn add(&mut self) {
-        self.binary_op(|a, b| a + b);
+    #[test]
+    fn program() {
+        let outputs = run(&[1002, 4, 3, 4, 33], vec![]).unwrap();
+        assert!(outputs.is_empty());
     }
 
-    fn mul(&mut self) {
-        self.binary_op(|a, b| a * b);
+    #[test]
+    fn echo() {
+        assert_eq!(run(&[3, 0, 4, 0, 99], vec![42]).unwrap(), vec![42]);
     }
 
-    fn binary_op<F>(&mut self, f: F) 
-        where F: Fn(i32, i32) -> i32 {
-        let result_reg = self.op_codes[self.p+3] as usize;
-        self.op_codes[result_reg] = f(self.param1(), self.param2());
-        self.p+=4;
-    }
-    
-    fn next_code(&mut self) -> i32 {
-        let (op_code, _ ,_ ,_ ) = Self::decode(self.op_codes[self.p]);
-        op_code
-    }
-    
-    fn decode(op_code: i32) -> (i32, ParameterMode, ParameterMode, ParameterMode) {
-        let data = format!("{:05}", op_code)
-            .chars()
-            .filter_map(|c| c.to_digit(10))
-            .collect::<Vec<u32>>();
-        
-        let operation : i32  = format!("{}{}", &data[3], &data[4]).parse().unwrap();
-        let p1 = if data[2] == 0 { Position } else { Immediate }; 
-        let p2 = if data[1] == 0 { Position } else { Immediate }; 
-        let p3 = if data[0] == 0 { Position } else { Immediate }; 
-        
-        (operation as i32 ,p1,p2,p3)
-    }
-    
-    fn param1(&self) -> i32 {
-        let (_,p1,_,_) = Self::decode(self.op_codes[self.p]);
-        let (p, op_codes) = (self.p, &self.op_codes);
-        if p1 == Position { op_codes[op_codes[p+1] as usize] } else { op_codes[p+1] }
+    #[test]
+    fn compare_to_eight() {
+        let larger = vec![3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,
+                          1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,
+                          999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99];
+        assert_eq!(run(&larger, vec![7]).unwrap(), vec![999]);
+        assert_eq!(run(&larger, vec![8]).unwrap(), vec![1000]);
+        assert_eq!(run(&larger, vec![9]).unwrap(), vec![1001]);
+
+        assert_eq!(run(&[3,9,8,9,10,9,4,9,99,-1,8], vec![8]).unwrap(), vec![1]);
+        assert_eq!(run(&[3,3,1107,-1,8,3,4,3,99], vec![9]).unwrap(), vec![0]);
     }
 
-    fn param2(&self) -> i32 {
-        let (_,_,p2,_) = Self::decode(self.op_codes[self.p]);
-        let (p, op_codes) = (self.p, &self.op_codes);
-        if p2 == Position { op_codes[op_codes[p+2] as usize] } else { op_codes[p+2] }
+    #[test]
+    fn short_input() {
+        let error = run(&[3, 0, 3, 1, 99], vec![1]).unwrap_err();
+        assert_eq!(error.to_string(), "Program asked for more input than it was given");
+        assert!(diagnostic(&[3, 0, 4, 0, 99], AIR_CONDITIONER).is_ok());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    
     #[test]
-    fn decode() {
-        let (code, op1, op2, op3) = Program::decode(1002);
-        assert_eq!(code, 2);
-        assert_eq!(op1, Position);
-        assert_eq!(op2, Immediate);
-        assert_eq!(op3, Position);
+    fn failed_checks() {
+        // Outputs 0, 3 and then 7
+        let program = [104, 0, 104, 3, 104, 7, 99];
+        assert_eq!(run(&program, vec![]).unwrap(), vec![0, 3, 7]);
+        let error = diagnostic(&program, AIR_CONDITIONER).unwrap_err();
+        assert_eq!(error.to_string(), "Check 2 failed with 3");
+        assert!(diagnostic(&[99], AIR_CONDITIONER).is_err());
+        assert_eq!(diagnostic(&[104, 0, 104, 9, 99], AIR_CONDITIONER).unwrap(), 9);
     }
-    
+
     #[test]
-    fn program() {
-        let mut program = Program::new(vec![1002, 4, 3, 4, 33], 1);
-        program.exec();
+    fn puzzle() {
+        let input : Vec<i64> = fs::read_to_string("input.txt").unwrap()
+            .trim()
+            .split(',')
+            .map(|x| x.parse::<i64>().unwrap())
+            .collect();
+        assert_eq!(diagnostic(&input, AIR_CONDITIONER).unwrap(), 7566643);
+        assert_eq!(diagnostic(&input, THERMAL_RADIATOR).unwrap(), 9265694);
     }
 }
-
@@ -1,8 +1,12 @@
-use std::fmt::Debug;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::fmt::{Display, Write};
+use std::hash::Hash;
 
 pub struct Graph<T> {
     nodes: Vec<NodeData<T>>,
     edges: Vec<EdgeData>,
+    index: HashMap<T, NodeIndex>,
 }
 
 pub type NodeIndex = usize;
@@ -10,19 +14,118 @@ pub type NodeIndex = usize;
 #[derive(Debug)]
 pub struct NodeData<T> {
     out: Option<EdgeIndex>,
+    incoming: Option<EdgeIndex>,
     pub node: T
 }
 
 
 pub type EdgeIndex = usize;
 
+pub type Weight = u64;
+
+/// Every edge is on two lists: the outgoing edges of its source and the
+/// incoming edges of its target, so it can be walked either way.
 #[derive(Debug, PartialEq)]
 pub struct EdgeData {
+    source: NodeIndex,
     target: NodeIndex,
-    next_edge: Option<EdgeIndex>
+    weight: Weight,
+    next_edge: Option<EdgeIndex>,
+    next_incoming: Option<EdgeIndex>
+}
+
+impl EdgeData {
+    pub fn source(&self) -> NodeIndex {
+        self.source
+    }
+
+    pub fn target(&self) -> NodeIndex {
+        self.target
+    }
+
+    pub fn weight(&self) -> Weight {
+        self.weight
+    }
+}
+
+/// Which way to follow edges.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Outgoing,
+    Incoming,
+    Both
+}
+
+/// The result of a walk from `start`: nodes in the order they were
+/// reached, and for each node the node it was reached from.
+#[derive(Debug)]
+pub struct Traversal {
+    pub start: NodeIndex,
+    pub order: Vec<NodeIndex>,
+    predecessors: Vec<Option<NodeIndex>>,
+    depths: Vec<Option<usize>>,
+}
+
+impl Traversal {
+    fn new(start: NodeIndex, size: usize) -> Traversal {
+        let mut depths = vec![None; size];
+        depths[start] = Some(0);
+        Traversal { start, order: vec![], predecessors: vec![None; size], depths }
+    }
+
+    pub fn reached(&self, node: NodeIndex) -> bool {
+        self.depths[node].is_some()
+    }
+
+    pub fn predecessor(&self, node: NodeIndex) -> Option<NodeIndex> {
+        self.predecessors[node]
+    }
+
+    /// Edges followed from `start` to reach `node`. For a BFS this is the
+    /// fewest possible.
+    pub fn depth(&self, node: NodeIndex) -> Option<usize> {
+        self.depths[node]
+    }
+
+    /// The nodes from `start` to `target` inclusive, if it was reached.
+    pub fn path_to(&self, target: NodeIndex) -> Option<Vec<NodeIndex>> {
+        self.depths[target]?;
+        let mut path = vec![target];
+        while let Some(previous) = self.predecessors[*path.last().unwrap()] {
+            path.push(previous);
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+/// Cheapest routes from `start` by total edge weight.
+#[derive(Debug)]
+pub struct ShortestPaths {
+    pub start: NodeIndex,
+    distances: Vec<Option<Weight>>,
+    predecessors: Vec<Option<NodeIndex>>,
+}
+
+impl ShortestPaths {
+    pub fn distance(&self, node: NodeIndex) -> Option<Weight> {
+        self.distances[node]
+    }
+
+    /// The nodes from `start` to `target` inclusive, if it can be reached.
+    pub fn path_to(&self, target: NodeIndex) -> Option<Vec<NodeIndex>> {
+        self.distances[target]?;
+        let mut path = vec![target];
+        while let Some(previous) = self.predecessors[*path.last().unwrap()] {
+            path.push(previous);
+        }
+        path.reverse();
+        Some(path)
+    }
 }
 
-impl <N : PartialEq + Debug> Graph<N> {
+impl <N : Eq + Hash + Clone> Graph<N> {
+
    
     pub fn node_at(&self, index: NodeIndex) -> &NodeData<N> {
         &self.nodes[index]
@@ -33,25 +136,313 @@ impl <N : PartialEq + Debug> Graph<N> {
             existing_index
         } else {
             let index = self.nodes.len();
-            self.nodes.push(NodeData { out: None, node });
+            self.index.insert(node.clone(), index);
+            self.nodes.push(NodeData { out: None, incoming: None, node });
             index
         }
     }
 
     pub fn add_edge(&mut self, source: NodeIndex, target: NodeIndex) {
+        self.add_weighted_edge(source, target, 1);
+    }
+
+    pub fn add_weighted_edge(&mut self, source: NodeIndex, target: NodeIndex, weight: Weight) -> EdgeIndex {
         let edge_index = self.edges.len();
-        let mut source_node = &mut self.nodes[source];
-        self.edges.push(EdgeData { target, next_edge: source_node.out });
-        source_node.out = Some(edge_index);
+        let next_edge = self.nodes[source].out;
+        let next_incoming = self.nodes[target].incoming;
+        self.edges.push(EdgeData { source, target, weight, next_edge, next_incoming });
+        self.nodes[source].out = Some(edge_index);
+        self.nodes[target].incoming = Some(edge_index);
+        edge_index
     }
 
-    pub fn successors(&self, source: NodeIndex) -> Successors<N> {
+    pub fn edge_at(&self, index: EdgeIndex) -> &EdgeData {
+        &self.edges[index]
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// The most recently added edge from `source` to `target`.
+    pub fn find_edge(&self, source: NodeIndex, target: NodeIndex) -> Option<EdgeIndex> {
+        self.out_edges(source).find(|&edge| self.edges[edge].target == target)
+    }
+
+    /// Indexes of the edges leaving `source`, newest first.
+    pub fn out_edges(&self, source: NodeIndex) -> impl Iterator<Item = EdgeIndex> + '_ {
+        std::iter::successors(self.nodes[source].out, move |&edge| self.edges[edge].next_edge)
+    }
+
+    /// Indexes of the edges arriving at `target`, newest first.
+    pub fn in_edges(&self, target: NodeIndex) -> impl Iterator<Item = EdgeIndex> + '_ {
+        std::iter::successors(self.nodes[target].incoming, move |&edge| self.edges[edge].next_incoming)
+    }
+
+    /// Removes an edge. The last edge takes its index, like `Vec::swap_remove`.
+    pub fn remove_edge(&mut self, edge: EdgeIndex) {
+        let EdgeData { source, target, next_edge, next_incoming, .. } = self.edges[edge];
+        self.relink_out(source, edge, next_edge);
+        self.relink_in(target, edge, next_incoming);
+
+        let last = self.edges.len() - 1;
+        if edge != last {
+            let EdgeData { source, target, .. } = self.edges[last];
+            self.relink_out(source, last, Some(edge));
+            self.relink_in(target, last, Some(edge));
+        }
+        self.edges.swap_remove(edge);
+    }
+
+    /// Removes a node and all of its edges, returning its value. The last
+    /// node takes its index, like `Vec::swap_remove`.
+    pub fn remove_node(&mut self, node: NodeIndex) -> N {
+        while let Some(edge) = self.nodes[node].out.or(self.nodes[node].incoming) {
+            self.remove_edge(edge);
+        }
+
+        let last = self.nodes.len() - 1;
+        if node != last {
+            let outgoing: Vec<EdgeIndex> = self.out_edges(last).collect();
+            let incoming: Vec<EdgeIndex> = self.in_edges(last).collect();
+            outgoing.into_iter().for_each(|edge| self.edges[edge].source = node);
+            incoming.into_iter().for_each(|edge| self.edges[edge].target = node);
+            self.index.insert(self.nodes[last].node.clone(), node);
+        }
+        let removed = self.nodes.swap_remove(node).node;
+        self.index.remove(&removed);
+        removed
+    }
+
+    /// Points whatever links to `old` on the outgoing list of `source` at
+    /// `new` instead.
+    fn relink_out(&mut self, source: NodeIndex, old: EdgeIndex, new: Option<EdgeIndex>) {
+        if self.nodes[source].out == Some(old) {
+            self.nodes[source].out = new;
+            return;
+        }
+        let previous = self.out_edges(source).find(|&e| self.edges[e].next_edge == Some(old));
+        if let Some(previous) = previous {
+            self.edges[previous].next_edge = new;
+        }
+    }
+
+    fn relink_in(&mut self, target: NodeIndex, old: EdgeIndex, new: Option<EdgeIndex>) {
+        if self.nodes[target].incoming == Some(old) {
+            self.nodes[target].incoming = new;
+            return;
+        }
+        let previous = self.in_edges(target).find(|&e| self.edges[e].next_incoming == Some(old));
+        if let Some(previous) = previous {
+            self.edges[previous].next_incoming = new;
+        }
+    }
+
+    pub fn successors(&self, source: NodeIndex) -> Successors<'_, N> {
         let first_outgoing_edge = self.nodes[source].out;
         Successors { graph: self, current_edge_index: first_outgoing_edge }
     }
+
+    pub fn predecessors(&self, target: NodeIndex) -> Predecessors<'_, N> {
+        let first_incoming_edge = self.nodes[target].incoming;
+        Predecessors { graph: self, current_edge_index: first_incoming_edge }
+    }
+
+    pub fn neighbours(&self, node: NodeIndex, direction: Direction) -> Vec<NodeIndex> {
+        match direction {
+            Direction::Outgoing => self.successors(node).collect(),
+            Direction::Incoming => self.predecessors(node).collect(),
+            Direction::Both => self.successors(node).chain(self.predecessors(node)).collect()
+        }
+    }
     
     pub fn find_node(&self, node: &N) -> Option<NodeIndex> {
-        self.nodes.iter().enumerate().filter_map(|(i,n)| if *node == n.node { Some(i as NodeIndex) } else { None }).nth(0)
+        self.index.get(node).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Breadth first from `start`, following edges.
+    pub fn bfs(&self, start: NodeIndex) -> Traversal {
+        self.bfs_in(start, Direction::Outgoing)
+    }
+
+    /// Breadth first from `start`, following edges in `direction`.
+    pub fn bfs_in(&self, start: NodeIndex, direction: Direction) -> Traversal {
+        let mut traversal = Traversal::new(start, self.nodes.len());
+        let mut queue = VecDeque::new();
+        queue.push_back(start);
+        while let Some(next) = queue.pop_front() {
+            traversal.order.push(next);
+            let depth = traversal.depths[next].map(|d| d + 1);
+            for successor in self.neighbours(next, direction) {
+                if !traversal.reached(successor) {
+                    traversal.depths[successor] = depth;
+                    traversal.predecessors[successor] = Some(next);
+                    queue.push_back(successor);
+                }
+            }
+        }
+        traversal
+    }
+
+    /// Edges followed from `start` to each node, if it can be reached.
+    pub fn bfs_distances(&self, start: NodeIndex) -> Vec<Option<usize>> {
+        self.bfs(start).depths
+    }
+
+    /// Dijkstra's algorithm from `start`, following edges. Distances too
+    /// long for a `Weight` come out as `Weight::MAX`.
+    pub fn dijkstra(&self, start: NodeIndex) -> ShortestPaths {
+        let mut paths = ShortestPaths {
+            start,
+            distances: vec![None; self.nodes.len()],
+            predecessors: vec![None; self.nodes.len()]
+        };
+        paths.distances[start] = Some(0);
+        let mut queue = BinaryHeap::new();
+        queue.push(Reverse((0, start)));
+        while let Some(Reverse((distance, node))) = queue.pop() {
+            if paths.distances[node].is_some_and(|best| distance > best) {
+                continue;
+            }
+            for edge in self.out_edges(node).map(|e| &self.edges[e]) {
+                let through = distance.saturating_add(edge.weight());
+                if paths.distances[edge.target].is_none_or(|best| through < best) {
+                    paths.distances[edge.target] = Some(through);
+                    paths.predecessors[edge.target] = Some(node);
+                    queue.push(Reverse((through, edge.target)));
+                }
+            }
+        }
+        paths
+    }
+
+    /// Every node after all of the nodes with edges into it, or None if
+    /// there is a cycle.
+    pub fn topological_sort(&self) -> Option<Vec<NodeIndex>> {
+        let mut waiting_on: Vec<usize> = (0..self.nodes.len()).map(|node| self.predecessors(node).count()).collect();
+        let mut ready: VecDeque<NodeIndex> = (0..self.nodes.len()).filter(|&node| waiting_on[node] == 0).collect();
+        let mut order = vec![];
+        while let Some(node) = ready.pop_front() {
+            order.push(node);
+            for successor in self.successors(node) {
+                waiting_on[successor] -= 1;
+                if waiting_on[successor] == 0 {
+                    ready.push_back(successor);
+                }
+            }
+        }
+        if order.len() == self.nodes.len() { Some(order) } else { None }
+    }
+
+    /// Groups of nodes joined by edges in either direction, each in BFS
+    /// order from its lowest index.
+    pub fn connected_components(&self) -> Vec<Vec<NodeIndex>> {
+        let mut in_component = vec![false; self.nodes.len()];
+        let mut components = vec![];
+        for node in 0..self.nodes.len() {
+            if !in_component[node] {
+                let component = self.bfs_in(node, Direction::Both).order;
+                component.iter().for_each(|&n| in_component[n] = true);
+                components.push(component);
+            }
+        }
+        components
+    }
+
+    /// Depth first from `start`, following edges, with an explicit stack so
+    /// long chains can't overflow the call stack.
+    pub fn dfs(&self, start: NodeIndex) -> Traversal {
+        let mut traversal = Traversal::new(start, self.nodes.len());
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack = vec![(start, None)];
+        while let Some((next, from)) = stack.pop() {
+            if visited[next] {
+                continue;
+            }
+            visited[next] = true;
+            if let Some(from) = from {
+                traversal.predecessors[next] = Some(from);
+                traversal.depths[next] = traversal.depths[from].map(|d| d + 1);
+            }
+            traversal.order.push(next);
+            for successor in self.successors(next) {
+                if !visited[successor] {
+                    stack.push((successor, Some(next)));
+                }
+            }
+        }
+        traversal
+    }
+}
+
+const HIGHLIGHT: &str = "color=red, penwidth=2";
+
+impl <N : Eq + Hash + Clone + Display> Graph<N> {
+    /// Graphviz DOT for the whole graph. Nodes in `path`, and the edges
+    /// joining consecutive ones in either direction, are drawn in red.
+    pub fn to_dot(&self, path: &[NodeIndex]) -> String {
+        let on_path: HashSet<NodeIndex> = path.iter().cloned().collect();
+        let steps: HashSet<(NodeIndex, NodeIndex)> = path.windows(2)
+            .flat_map(|w| vec![(w[0], w[1]), (w[1], w[0])])
+            .collect();
+
+        let mut dot = String::from("digraph {\n");
+        for (index, data) in self.nodes.iter().enumerate() {
+            let label = data.node.to_string().replace('\\', "\\\\").replace('"', "\\\"");
+            let style = if on_path.contains(&index) { format!(", {}", HIGHLIGHT) } else { String::new() };
+            dot.push_str(&format!("    n{} [label=\"{}\"{}];\n", index, label, style));
+        }
+        for edge in &self.edges {
+            let style = if steps.contains(&(edge.source, edge.target)) { format!(" [{}]", HIGHLIGHT) } else { String::new() };
+            dot.push_str(&format!("    n{} -> n{}{};\n", edge.source, edge.target, style));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// An indented tree of everything reachable from `root`, each node
+    /// followed by its depth. A node reachable more than once is shown
+    /// once, under the first parent to list it.
+    pub fn ascii_tree(&self, root: NodeIndex) -> String {
+        let mut tree = String::new();
+        // Nodes are claimed when pushed, so every child pushed gets printed
+        // and the last one pushed really is the last line under its parent
+        let mut seen = vec![false; self.nodes.len()];
+        seen[root] = true;
+        // Node, depth, the prefix of its line, and whether it is the last
+        // child of its parent, which the root isn't
+        let mut stack = vec![(root, 0, String::new(), None)];
+        while let Some((node, depth, prefix, last)) = stack.pop() {
+            let (connector, indent) = match last {
+                None => ("", ""),
+                Some(true) => ("└── ", "    "),
+                Some(false) => ("├── ", "│   ")
+            };
+            writeln!(tree, "{}{}{} ({})", prefix, connector, self.nodes[node].node, depth).unwrap();
+
+            // Successors come newest first, so pushing them in that order
+            // pops them in the order they were added
+            let prefix = prefix + indent;
+            let mut children: Vec<NodeIndex> = vec![];
+            for child in self.successors(node) {
+                if !seen[child] {
+                    seen[child] = true;
+                    children.push(child);
+                }
+            }
+            for (i, &child) in children.iter().enumerate() {
+                stack.push((child, depth + 1, prefix.clone(), Some(i == 0)));
+            }
+        }
+        tree
     }
 }
 
@@ -75,8 +466,194 @@ impl<'graph, T> Iterator for Successors<'graph, T> {
     }
 }
 
-impl <T> Default for Graph<T> {
+pub struct Predecessors<'graph, T> {
+    graph: &'graph Graph<T>,
+    current_edge_index: Option<EdgeIndex>,
+}
+
+impl<'graph, T> Iterator for Predecessors<'graph, T> {
+    type Item = NodeIndex;
+
+    fn next(&mut self) -> Option<NodeIndex> {
+        let edge = &self.graph.edges[self.current_edge_index?];
+        self.current_edge_index = edge.next_incoming;
+        Some(edge.source)
+    }
+}
+
+impl <T : Eq + Hash> Default for Graph<T> {
     fn default() -> Self {
-        Graph { nodes: vec![], edges: vec![] }
+        Graph { nodes: vec![], edges: vec![], index: HashMap::new() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a -> b -> d, a -> c -> d, d -> e
+    fn diamond() -> Graph<&'static str> {
+        let mut graph = Graph::default();
+        for (from, to) in &[("a", "b"), ("a", "c"), ("b", "d"), ("c", "d"), ("d", "e")] {
+            let from = graph.add_node(*from);
+            let to = graph.add_node(*to);
+            graph.add_edge(from, to);
+        }
+        graph
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn finds_nodes() {
+        let graph = diamond();
+        assert_eq!(graph.len(), 5);
+        assert_eq!(graph.find_node(&"d"), Some(3));
+        assert_eq!(graph.find_node(&"z"), None);
+    }
+
+    #[test]
+    fn bfs() {
+        let graph = diamond();
+        let traversal = graph.bfs(0);
+        assert_eq!(traversal.order.len(), 5);
+        assert_eq!(traversal.depth(4), Some(3));
+        assert_eq!(traversal.path_to(4).unwrap().len(), 4);
+        assert_eq!(graph.bfs(4).path_to(0), None);
+        assert_eq!(graph.bfs_in(4, Direction::Incoming).depth(0), Some(3));
+        assert_eq!(graph.bfs_in(1, Direction::Both).depth(2), Some(2));
+    }
+
+    #[test]
+    fn both_ways() {
+        let graph = diamond();
+        let mut into_d = graph.predecessors(3).collect::<Vec<_>>();
+        into_d.sort();
+        assert_eq!(into_d, vec![1, 2]);
+        assert_eq!(graph.successors(3).collect::<Vec<_>>(), vec![4]);
+        assert_eq!(graph.neighbours(3, Direction::Both).len(), 3);
+        assert_eq!(graph.predecessors(0).count(), 0);
+    }
+
+    #[test]
+    fn dfs() {
+        let graph = diamond();
+        let traversal = graph.dfs(0);
+        assert_eq!(traversal.order[0], 0);
+        assert_eq!(traversal.order.len(), 5);
+        assert_eq!(traversal.path_to(4).map(|p| p.len()), Some(4));
+        assert!(!graph.dfs(3).reached(0));
+    }
+
+    #[test]
+    fn weighted() {
+        let mut graph = Graph::default();
+        let (a, b, c, d) = (graph.add_node('a'), graph.add_node('b'), graph.add_node('c'), graph.add_node('d'));
+        graph.add_weighted_edge(a, b, 5);
+        graph.add_weighted_edge(a, c, 1);
+        let c_to_b = graph.add_weighted_edge(c, b, 2);
+        graph.add_weighted_edge(b, d, 1);
+
+        let paths = graph.dijkstra(a);
+        assert_eq!(paths.distance(b), Some(3));
+        assert_eq!(paths.distance(d), Some(4));
+        assert_eq!(paths.path_to(d), Some(vec![a, c, b, d]));
+        assert_eq!(graph.dijkstra(d).distance(a), None);
+        assert_eq!(graph.bfs_distances(a), vec![Some(0), Some(1), Some(1), Some(2)]);
+
+        assert_eq!(graph.find_edge(c, b), Some(c_to_b));
+        graph.remove_edge(c_to_b);
+        assert_eq!(graph.find_edge(c, b), None);
+        // b -> d moved into the removed edge's slot
+        let moved = graph.edge_at(c_to_b);
+        assert_eq!((moved.source(), moved.target(), moved.weight()), (b, d, 1));
+        assert_eq!(graph.dijkstra(a).distance(d), Some(6));
+
+        let heavy = graph.add_node('e');
+        graph.add_weighted_edge(d, heavy, Weight::MAX);
+        assert_eq!(graph.dijkstra(a).distance(heavy), Some(Weight::MAX));
+    }
+
+    #[test]
+    fn remove_node() {
+        let mut graph = diamond();
+        assert_eq!(graph.remove_node(1), "b");
+
+        // e moves into the gap left by b
+        assert_eq!((graph.len(), graph.edge_count()), (4, 3));
+        assert_eq!(graph.find_node(&"b"), None);
+        assert_eq!(graph.find_node(&"e"), Some(1));
+        assert_eq!(graph.successors(0).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(graph.predecessors(3).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(graph.successors(3).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(graph.bfs_distances(0), vec![Some(0), Some(3), Some(1), Some(2)]);
+
+        assert_eq!(graph.remove_node(1), "e");
+        assert_eq!(graph.find_node(&"d"), Some(1));
+        assert_eq!(graph.successors(1).count(), 0);
+        assert_eq!(graph.remove_node(0), "a");
+        assert_eq!(graph.edge_count(), 1);
+        assert_eq!(graph.predecessors(1).collect::<Vec<_>>(), vec![graph.find_node(&"c").unwrap()]);
+    }
+
+    #[test]
+    fn topological_sort() {
+        let mut graph = diamond();
+        let order = graph.topological_sort().unwrap();
+        let position = |node| order.iter().position(|&n| n == node).unwrap();
+        for (from, to) in &[(0, 1), (0, 2), (1, 3), (2, 3), (3, 4)] {
+            assert!(position(*from) < position(*to));
+        }
+        graph.add_edge(4, 1);
+        assert_eq!(graph.topological_sort(), None);
+    }
+
+    #[test]
+    fn connected_components() {
+        let mut graph = diamond();
+        let x = graph.add_node("x");
+        let y = graph.add_node("y");
+        graph.add_edge(y, x);
+        graph.add_node("z");
+        let sizes: Vec<usize> = graph.connected_components().iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![5, 2, 1]);
+    }
+
+    #[test]
+    fn dot() {
+        let graph = diamond();
+        assert_eq!(graph.to_dot(&[3, 1, 0]), "digraph {
+    n0 [label=\"a\", color=red, penwidth=2];
+    n1 [label=\"b\", color=red, penwidth=2];
+    n2 [label=\"c\"];
+    n3 [label=\"d\", color=red, penwidth=2];
+    n4 [label=\"e\"];
+    n0 -> n1 [color=red, penwidth=2];
+    n0 -> n2;
+    n1 -> n3 [color=red, penwidth=2];
+    n2 -> n3;
+    n3 -> n4;
+}
+");
+        assert!(!graph.to_dot(&[]).contains("red"));
+    }
+
+    #[test]
+    fn ascii_tree() {
+        let graph = diamond();
+        assert_eq!(graph.ascii_tree(0), "\
+a (0)
+├── b (1)
+│   └── d (2)
+│       └── e (3)
+└── c (1)
+");
+        assert_eq!(graph.ascii_tree(3), "d (0)\n└── e (1)\n");
+
+        // c is listed by a before b gets to it, so b has no children left
+        let mut graph = Graph::default();
+        let (a, b, c) = (graph.add_node('a'), graph.add_node('b'), graph.add_node('c'));
+        graph.add_edge(a, b);
+        graph.add_edge(a, c);
+        graph.add_edge(b, c);
+        assert_eq!(graph.ascii_tree(a), "a (0)\n├── b (1)\n└── c (1)\n");
+    }
+}
@@ -1,113 +1,117 @@
-use std::str::FromStr;
-use crate::graph::{Graph, NodeData};
+use crate::graph::{Direction, NodeData, NodeIndex};
+use crate::orbit_map::{OrbitMap, Root};
+use crate::tree::Tree;
 use std::fs;
 use std::fmt::{Display, Formatter, Error};
+use std::env;
+use std::time::Instant;
 
 pub mod graph;
+pub mod tree;
+pub mod orbit_map;
 
 const ROOT : &str =  "COM";
+const BENCH_SIZE : usize = 100_000;
 
+/// `--root NAME` names the object everything orbits, which is otherwise
+/// inferred. `--dot` prints the map as Graphviz DOT with the transfer from
+/// YOU to SAN picked out, and `--tree` prints it as an indented tree.
+/// `--bench [size]` times parsing and a transfer on a synthetic map instead.
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("--bench") {
+        let size = args.get(1).map(|s| s.parse().expect("size should be a number")).unwrap_or(BENCH_SIZE);
+        bench(size);
+        return;
+    }
+    let root = match args.iter().position(|arg| arg == "--root") {
+        Some(i) => Root::Named(args.get(i + 1).expect("--root needs a name").clone()),
+        None => Root::Infer
+    };
+
     let input : String = fs::read_to_string("input.txt")
         .expect("Something went wrong reading the file");
 
-    eprintln!("input = {:#?}", input);
+    let map = match OrbitMap::parse(&input, &root) {
+        Ok(map) => map,
+        Err(errors) => {
+            errors.iter().for_each(|e| eprintln!("{}", e));
+            std::process::exit(1);
+        }
+    };
 
-    let graph: Graph<String> = input.parse().expect("Not a graph");
-    
-    //no longer works after part2 since the directionality was flipped to make back tracking easy 
-    let orbits = total_orbits(&graph);
+    if args.iter().any(|arg| arg == "--dot") {
+        print!("{}", map.graph.to_dot(&transfer_path(&map).unwrap_or_default()));
+        return;
+    }
+    if args.iter().any(|arg| arg == "--tree") {
+        print!("{}", map.graph.ascii_tree(map.root));
+        return;
+    }
+
+    let orbits = total_orbits(&map);
     eprintln!("orbits = {:#?}", orbits);
 
-    let transfers = orbital_transfer(&graph);
+    let transfers = orbital_transfer(&map);
     eprintln!("transfers = {:#?}", transfers);
 }
 
-fn orbital_transfer(graph : &Graph<String>) -> usize {
-    let you_route = path_to(&graph, "YOU", ROOT);
-    let san_route = path_to(&graph, "SAN", ROOT);
-    
-    // count to a common ancestor from YOU
-    let count1 = you_route.iter()
-        .take_while(|&&node| !san_route.iter().any(|&n| n.node == node.node))
-        .count();
-
-    // count to a common ancestor from SAN
-    let count2 = san_route.iter()
-        .take_while(|&&node| !you_route.iter().any(|&n| n.node == node.node))
-        .count();
-     
-    count1 + count2 - 2
+/// Transfers between the objects YOU and SAN are orbiting.
+fn orbital_transfer(map: &OrbitMap) -> usize {
+    let tree = orbit_tree(map);
+    let you = map.find("YOU").expect("Missing YOU");
+    let san = map.find("SAN").expect("Missing SAN");
+    tree.distance(you, san).expect("YOU and SAN should both orbit the root") - 2
 }
 
-fn total_orbits(graph: &Graph<String>) -> usize {
-    let root = graph.find_node(&ROOT.to_string()).expect("Missing root");
-    let mut visited = vec![];
-    total_orbits_recur(graph, &mut visited, root, 0)
+/// The objects from YOU to SAN, if both are on the map.
+fn transfer_path(map: &OrbitMap) -> Option<Vec<NodeIndex>> {
+    let you = map.find("YOU")?;
+    let san = map.find("SAN")?;
+    map.graph.bfs_in(you, Direction::Both).path_to(san)
 }
 
-fn  path_to<'graph>(graph: &'graph Graph<String>, source: &str, target: &str) -> Vec<&'graph NodeData<String>> {
-    let root = graph.find_node(&source.to_string()).expect("Missing root");
-    let mut visited = vec![];
-    path_to_recur(graph, target, &mut visited, root, vec![])
+fn total_orbits(map: &OrbitMap) -> usize {
+    orbit_tree(map).total_depth()
 }
 
-fn path_to_recur<'graph>(graph: &'graph Graph<String>, target: &str, visited: &mut Vec<usize>, next: usize, path: Vec<&'graph NodeData<String>>) -> Vec<&'graph NodeData<String>> {
-    visited.push(next);
-    let mut new_path = path.clone();
-    let mut result = vec![];
-    let node = graph.node_at(next);
-    new_path.push(node);
-
-    if &node.node == target {
-        result = new_path;
-    } else {
-        for successor in graph.successors(next) {
-            if !visited.contains(&successor) {
-                let mut successor_path = path_to_recur(graph, target, visited, successor, new_path.clone());
-                result.append(&mut successor_path);
-            }
-        }
-    }
-    result
+fn orbit_tree(map: &OrbitMap) -> Tree {
+    Tree::new(&map.graph, map.root)
 }
 
-fn total_orbits_recur(graph: &Graph<String>, visited: &mut Vec<usize>, next: usize, depth: usize) -> usize {
-    visited.push(next);
-    let mut total = depth;
+/// An orbit map with `size` objects besides COM, where object i orbits
+/// i / 2 every third object and i - 1 otherwise, so it has long chains.
+/// YOU orbits the last object and SAN one a seventh of the way in.
+fn synthetic(size: usize) -> String {
+    let name = |i: usize| if i == 0 { ROOT.to_string() } else { format!("O{}", i) };
+    let mut lines: Vec<String> = (1..=size)
+        .map(|i| format!("{}){}", name(if i.is_multiple_of(3) { i / 2 } else { i - 1 }), name(i)))
+        .collect();
+    lines.push(format!("{})YOU", name(size)));
+    lines.push(format!("{})SAN", name(size / 7)));
+    lines.join("\n")
+}
 
-    for successor in graph.successors(next) {
-        if !visited.contains(&successor) {
-            total += total_orbits_recur(graph, visited, successor, depth + 1);
-        }
-    }
+fn bench(size: usize) {
+    let input = synthetic(size);
+    let start = Instant::now();
+    let map: OrbitMap = input.parse().expect("Not an orbit map");
+    eprintln!("parsed {} objects in {:?}", map.graph.len(), start.elapsed());
 
-    total
+    let start = Instant::now();
+    let transfers = orbital_transfer(&map);
+    eprintln!("transfers = {} in {:?}", transfers, start.elapsed());
 }
 
-impl FromStr for Graph<String> {
-    type Err = ();
-    
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut g : Graph<String> = Default::default();
-        s.lines()
-            .for_each(|line| {
-                let nodes = line.split(")").collect::<Vec<&str>>();
-                
-                let node1_index = g.add_node(nodes[1].to_string());
-                let node2_index = g.add_node(nodes[0].to_string());
-                g.add_edge(node1_index, node2_index)
-            });
-        
-        Ok(g)
+impl Display for NodeData<String> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}", self.node)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::graph::NodeIndex;
 
     const INPUT: &str = "COM)B
 B)C
@@ -123,15 +127,15 @@ K)L";
 
     #[test]
     fn graph() {
-        let test_graph: Graph<String> = INPUT.parse().expect("Not a graph");
+        let map: OrbitMap = INPUT.parse().expect("Not an orbit map");
 
-        assert_eq!(test_graph.successors(1).collect::<Vec<_>>().len(), 2);
+        assert_eq!(map.graph.successors(1).collect::<Vec<_>>().len(), 2);
     }
     
     #[test]
     fn count() {
-        let test_graph: Graph<String> = INPUT.parse().expect("Not a graph");
-        let total_orbits = total_orbits(&test_graph);
+        let map: OrbitMap = INPUT.parse().expect("Not an orbit map");
+        let total_orbits = total_orbits(&map);
         assert_eq!(total_orbits, 42);
     }
     
@@ -151,34 +155,68 @@ I)SAN";
     
     #[test]
     fn test2() {
-        let test_graph: Graph<String> = INPUT2.parse().expect("Not a graph");
-        let total_orbits = total_orbits(&test_graph);
+        let map: OrbitMap = INPUT2.parse().expect("Not an orbit map");
+        assert_eq!(total_orbits(&map), 54);
     }
     
     #[test]
     fn path() {
-        let graph: Graph<String> = INPUT2.parse().expect("Not a graph");
-        let all = path_to(&graph, "H", ROOT);
-        
-        for node in all {
-            eprintln!("node = {}", node);
-        }
+        let map: OrbitMap = INPUT2.parse().expect("Not an orbit map");
+        let h = map.find("H").unwrap();
+        let names: Vec<String> = orbit_tree(&map).ancestors(h).map(|node| map.graph.node_at(node).to_string()).collect();
+        assert_eq!(names, vec!["G", "B", "COM"]);
     }
     
     #[test]
     fn orbital_transfore() {
-        let graph: Graph<String> = INPUT2.parse().expect("Not a graph");
-        let all = orbital_transfer(&graph);
-
-        
+        let map: OrbitMap = INPUT2.parse().expect("Not an orbit map");
+        assert_eq!(orbital_transfer(&map), 4);
+        assert_eq!(total_orbits(&map), 54);
     }
-}
 
-impl Display for NodeData<String> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        write!(f, "{}", self.node)
+    #[test]
+    fn rendering() {
+        let map: OrbitMap = INPUT2.parse().expect("Not an orbit map");
+        let path: Vec<String> = transfer_path(&map).unwrap().iter().map(|&n| map.graph.node_at(n).to_string()).collect();
+        assert_eq!(path, vec!["YOU", "K", "J", "E", "D", "I", "SAN"]);
+
+        let dot = map.graph.to_dot(&transfer_path(&map).unwrap());
+        assert_eq!(dot.matches("->").count(), 13);
+        assert_eq!(dot.matches("red").count(), 7 + 6);
+
+        let tree = map.graph.ascii_tree(map.root);
+        assert!(tree.starts_with("COM (0)\n└── B (1)\n    ├── C (2)\n"));
+        assert!(tree.contains("    │       │           └── YOU (7)\n"));
+        assert_eq!(tree.lines().count(), 14);
     }
-}
 
+    #[test]
+    fn puzzle() {
+        let map: OrbitMap = fs::read_to_string("input.txt").unwrap().parse().unwrap();
+        assert_eq!(map.graph.node_at(map.root).node, ROOT);
+        assert_eq!(total_orbits(&map), 119831);
+        assert_eq!(orbital_transfer(&map), 322);
+    }
 
+    #[test]
+    fn synthetic_map() {
+        let map: OrbitMap = synthetic(BENCH_SIZE).parse().unwrap();
+        assert_eq!(map.graph.len(), BENCH_SIZE + 3);
+
+        // Work out the answer from the generator's parent rule
+        let parent = |i: usize| if i.is_multiple_of(3) { i / 2 } else { i - 1 };
+        let ancestors = |mut i: usize| {
+            let mut all = vec![i];
+            while i != 0 {
+                i = parent(i);
+                all.push(i);
+            }
+            all
+        };
+        let (you, san) = (ancestors(BENCH_SIZE), ancestors(BENCH_SIZE / 7));
+        let common = you.iter().position(|i| san.contains(i)).unwrap();
+        let expected = common + san.iter().position(|i| *i == you[common]).unwrap();
 
+        assert_eq!(orbital_transfer(&map), expected);
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::graph::{Graph, NodeIndex};
+
+/// Which object everything else orbits.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Root {
+    /// The only object that doesn't orbit anything.
+    Infer,
+    Named(String)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrbitError {
+    Malformed { line: usize, content: String },
+    /// `object` orbits `first` on one line and `second` on another.
+    TwoParents { object: String, first: (String, usize), second: (String, usize) },
+    Cycle { objects: Vec<String> },
+    NoRoot,
+    MultipleRoots { roots: Vec<String> },
+    MissingRoot { root: String },
+    /// Objects that don't end up orbiting the root.
+    Orphans { root: String, objects: Vec<String> }
+}
+
+impl Display for OrbitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrbitError::Malformed { line, content } =>
+                write!(f, "line {}: expected PARENT)CHILD but found {:?}", line, content),
+            OrbitError::TwoParents { object, first: (a, line_a), second: (b, line_b) } =>
+                write!(f, "line {}: {} orbits {}, but already orbits {} on line {}", line_b, object, b, a, line_a),
+            OrbitError::Cycle { objects } =>
+                write!(f, "orbits go round in a circle: {}", objects.join(" -> ")),
+            OrbitError::NoRoot =>
+                write!(f, "every object orbits something, so there's no root"),
+            OrbitError::MultipleRoots { roots } =>
+                write!(f, "found {} roots, expected one: {}", roots.len(), roots.join(", ")),
+            OrbitError::MissingRoot { root } =>
+                write!(f, "root {} isn't in the map", root),
+            OrbitError::Orphans { root, objects } =>
+                write!(f, "{} objects don't orbit {}: {}", objects.len(), root, objects.join(", "))
+        }
+    }
+}
+
+/// An orbit map that has been checked to be a single tree.
+pub struct OrbitMap {
+    pub graph: Graph<String>,
+    pub root: NodeIndex
+}
+
+impl OrbitMap {
+    /// Parses and validates `input`, reporting every problem found rather
+    /// than just the first.
+    pub fn parse(input: &str, root: &Root) -> Result<OrbitMap, Vec<OrbitError>> {
+        let mut errors = vec![];
+        let mut graph: Graph<String> = Graph::default();
+        let mut parent_of: HashMap<NodeIndex, (NodeIndex, usize)> = HashMap::new();
+
+        for (i, line) in input.lines().enumerate() {
+            let (line_number, content) = (i + 1, line.trim());
+            if content.is_empty() {
+                continue;
+            }
+            let (parent, child) = match content.split(')').collect::<Vec<_>>().as_slice() {
+                [parent, child] if !parent.is_empty() && !child.is_empty() => (parent.to_string(), child.to_string()),
+                _ => {
+                    errors.push(OrbitError::Malformed { line: line_number, content: content.to_string() });
+                    continue;
+                }
+            };
+
+            let parent = graph.add_node(parent);
+            let child = graph.add_node(child);
+            match parent_of.get(&child) {
+                Some(&(existing, _)) if existing == parent => {},
+                Some(&(existing, first_line)) => errors.push(OrbitError::TwoParents {
+                    object: graph.node_at(child).node.clone(),
+                    first: (graph.node_at(existing).node.clone(), first_line),
+                    second: (graph.node_at(parent).node.clone(), line_number)
+                }),
+                None => {
+                    parent_of.insert(child, (parent, line_number));
+                    graph.add_edge(parent, child);
+                }
+            }
+        }
+
+        let name = |node: NodeIndex| graph.node_at(node).node.clone();
+        let parent = |node: NodeIndex| parent_of.get(&node).map(|&(parent, _)| parent);
+
+        // Follow parents from every object, marking objects on the walk in
+        // progress. Reaching one of those again means a cycle.
+        let mut in_cycle = vec![false; graph.len()];
+        let mut state = vec![0u8; graph.len()];
+        for start in 0..graph.len() {
+            let mut walk = vec![];
+            let mut next = Some(start);
+            while let Some(node) = next.filter(|&n| state[n] == 0) {
+                state[node] = 1;
+                walk.push(node);
+                next = parent(node);
+            }
+            if let Some(repeated) = next.filter(|&n| state[n] == 1) {
+                let from = walk.iter().position(|&n| n == repeated).unwrap();
+                let cycle = &walk[from..];
+                cycle.iter().for_each(|&n| in_cycle[n] = true);
+                // Listed parent first, starting where the walk came in
+                let objects = std::iter::once(&repeated).chain(cycle[1..].iter().rev()).map(|&n| name(n)).collect();
+                errors.push(OrbitError::Cycle { objects });
+            }
+            walk.iter().for_each(|&n| state[n] = 2);
+        }
+
+        let root = match root {
+            Root::Named(root) => graph.find_node(root).ok_or_else(|| OrbitError::MissingRoot { root: root.clone() }),
+            Root::Infer => {
+                let roots: Vec<NodeIndex> = (0..graph.len()).filter(|&n| parent(n).is_none()).collect();
+                match roots.as_slice() {
+                    [root] => Ok(*root),
+                    [] => Err(OrbitError::NoRoot),
+                    _ => Err(OrbitError::MultipleRoots { roots: roots.iter().map(|&n| name(n)).collect() })
+                }
+            }
+        };
+
+        match root {
+            Ok(root) => {
+                let reached = graph.bfs(root);
+                let orphans: Vec<String> = (0..graph.len())
+                    .filter(|&n| !reached.reached(n) && !in_cycle[n])
+                    .map(name)
+                    .collect();
+                if !orphans.is_empty() {
+                    errors.push(OrbitError::Orphans { root: name(root), objects: orphans });
+                }
+                if errors.is_empty() {
+                    return Ok(OrbitMap { graph, root });
+                }
+            },
+            Err(e) => errors.push(e)
+        }
+        Err(errors)
+    }
+
+    pub fn find(&self, name: &str) -> Option<NodeIndex> {
+        self.graph.find_node(&name.to_string())
+    }
+}
+
+/// Parses with the root inferred.
+impl FromStr for OrbitMap {
+    type Err = Vec<OrbitError>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OrbitMap::parse(s, &Root::Infer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(input: &str, root: Root) -> Vec<String> {
+        OrbitMap::parse(input, &root).err().unwrap().iter().map(OrbitError::to_string).collect()
+    }
+
+    #[test]
+    fn infers_root() {
+        let map: OrbitMap = "B)C\nCOM)B\n\nB)D\n".parse().unwrap();
+        assert_eq!(map.graph.node_at(map.root).node, "COM");
+        assert_eq!(map.graph.len(), 4);
+    }
+
+    #[test]
+    fn configured_root() {
+        let map = OrbitMap::parse("X)Y\nY)Z", &Root::Named("X".to_string())).unwrap();
+        assert_eq!(map.root, map.find("X").unwrap());
+        assert_eq!(errors("X)Y\nY)Z", Root::Named("Y".to_string())), vec!["1 objects don't orbit Y: X"]);
+        assert_eq!(errors("X)Y", Root::Named("COM".to_string())), vec!["root COM isn't in the map"]);
+    }
+
+    #[test]
+    fn malformed_lines() {
+        assert_eq!(errors("COM)B\nB-C\nB)\nC)D)E", Root::Infer), vec![
+            "line 2: expected PARENT)CHILD but found \"B-C\"",
+            "line 3: expected PARENT)CHILD but found \"B)\"",
+            "line 4: expected PARENT)CHILD but found \"C)D)E\""]);
+    }
+
+    #[test]
+    fn two_parents() {
+        assert_eq!(errors("COM)A\nCOM)B\nA)C\nB)C\nA)C", Root::Infer),
+                   vec!["line 4: C orbits B, but already orbits A on line 3"]);
+    }
+
+    #[test]
+    fn cycles() {
+        assert_eq!(errors("COM)A\nB)C\nC)D\nD)B\nD)E", Root::Infer), vec![
+            "orbits go round in a circle: B -> C -> D",
+            "1 objects don't orbit COM: E"]);
+        assert_eq!(errors("A)B\nB)A", Root::Infer), vec![
+            "orbits go round in a circle: A -> B",
+            "every object orbits something, so there's no root"]);
+        assert_eq!(errors("COM)B\nA)A", Root::Infer), vec!["orbits go round in a circle: A"]);
+    }
+
+    #[test]
+    fn multiple_roots() {
+        assert_eq!(errors("COM)A\nX)B\nY)C", Root::Infer), vec!["found 3 roots, expected one: COM, X, Y"]);
+    }
+}
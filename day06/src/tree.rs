@@ -0,0 +1,210 @@
+use std::hash::Hash;
+use crate::graph::{Graph, NodeIndex};
+
+/// A graph seen as a tree hanging from `root`, following edges outwards.
+/// Depths, subtree sizes and ancestor jump tables are worked out once, so
+/// ancestor and distance queries take O(log n).
+pub struct Tree {
+    root: NodeIndex,
+    parents: Vec<Option<NodeIndex>>,
+    depths: Vec<Option<usize>>,
+    subtree_sizes: Vec<usize>,
+    /// `jumps[k][node]` is the ancestor 2^k levels above node, or the root
+    /// if that is further than the root.
+    jumps: Vec<Vec<NodeIndex>>,
+}
+
+impl Tree {
+    pub fn new<N: Eq + Hash + Clone>(graph: &Graph<N>, root: NodeIndex) -> Tree {
+        let traversal = graph.bfs(root);
+        let parents: Vec<Option<NodeIndex>> = (0..graph.len()).map(|node| traversal.predecessor(node)).collect();
+        let depths: Vec<Option<usize>> = (0..graph.len()).map(|node| traversal.depth(node)).collect();
+
+        // Children come after their parents in BFS order, so walking it
+        // backwards sees every subtree complete before its parent
+        let mut subtree_sizes = vec![0; graph.len()];
+        for &node in traversal.order.iter().rev() {
+            subtree_sizes[node] += 1;
+            if let Some(parent) = parents[node] {
+                subtree_sizes[parent] += subtree_sizes[node];
+            }
+        }
+
+        let max_depth = depths.iter().filter_map(|&d| d).max().unwrap_or(0);
+        let levels = (usize::BITS - max_depth.leading_zeros()).max(1) as usize;
+        let mut jumps = vec![(0..graph.len()).map(|node| parents[node].unwrap_or(root)).collect::<Vec<_>>()];
+        for k in 1..levels {
+            let previous = &jumps[k - 1];
+            let next = previous.iter().map(|&halfway| previous[halfway]).collect();
+            jumps.push(next);
+        }
+
+        Tree { root, parents, depths, subtree_sizes, jumps }
+    }
+
+    pub fn root(&self) -> NodeIndex {
+        self.root
+    }
+
+    pub fn parent(&self, node: NodeIndex) -> Option<NodeIndex> {
+        self.parents[node]
+    }
+
+    /// How far below the root `node` is, or None if it isn't in the tree.
+    pub fn depth(&self, node: NodeIndex) -> Option<usize> {
+        self.depths[node]
+    }
+
+    /// The sum of every depth, which for an orbit map is the total number
+    /// of direct and indirect orbits.
+    pub fn total_depth(&self) -> usize {
+        self.depths.iter().filter_map(|&d| d).sum()
+    }
+
+    /// How many nodes hang from `node`, itself included.
+    pub fn subtree_size(&self, node: NodeIndex) -> usize {
+        self.subtree_sizes[node]
+    }
+
+    /// From the parent of `node` up to the root.
+    pub fn ancestors(&self, node: NodeIndex) -> Ancestors<'_> {
+        Ancestors { tree: self, next: self.parents[node] }
+    }
+
+    /// The ancestor `levels` above `node`.
+    pub fn ancestor(&self, node: NodeIndex, levels: usize) -> Option<NodeIndex> {
+        if levels > self.depth(node)? {
+            return None;
+        }
+        Some((0..self.jumps.len())
+            .filter(|k| levels & (1 << k) != 0)
+            .fold(node, |node, k| self.jumps[k][node]))
+    }
+
+    /// The lowest common ancestor, where `a` and `b` are counted as their
+    /// own ancestors.
+    pub fn lca(&self, a: NodeIndex, b: NodeIndex) -> Option<NodeIndex> {
+        let (depth_a, depth_b) = (self.depth(a)?, self.depth(b)?);
+        let (mut a, mut b) = if depth_a >= depth_b {
+            (self.ancestor(a, depth_a - depth_b)?, b)
+        } else {
+            (a, self.ancestor(b, depth_b - depth_a)?)
+        };
+        if a == b {
+            return Some(a);
+        }
+        for level in self.jumps.iter().rev() {
+            if level[a] != level[b] {
+                a = level[a];
+                b = level[b];
+            }
+        }
+        self.parent(a)
+    }
+
+    /// Edges between `a` and `b`.
+    pub fn distance(&self, a: NodeIndex, b: NodeIndex) -> Option<usize> {
+        let lca = self.lca(a, b)?;
+        Some(self.depth(a)? + self.depth(b)? - 2 * self.depth(lca)?)
+    }
+}
+
+pub struct Ancestors<'tree> {
+    tree: &'tree Tree,
+    next: Option<NodeIndex>,
+}
+
+impl<'tree> Iterator for Ancestors<'tree> {
+    type Item = NodeIndex;
+
+    fn next(&mut self) -> Option<NodeIndex> {
+        let node = self.next?;
+        self.next = self.tree.parent(node);
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::Direction;
+
+    fn graph(edges: &[(usize, usize)]) -> Graph<usize> {
+        let mut graph = Graph::default();
+        for &(parent, child) in edges {
+            let parent = graph.add_node(parent);
+            let child = graph.add_node(child);
+            graph.add_edge(parent, child);
+        }
+        graph
+    }
+
+    #[test]
+    fn queries() {
+        //       0
+        //      / \
+        //     1   2
+        //    / \   \
+        //   3   4   5
+        //           |
+        //           6
+        let g = graph(&[(0, 1), (0, 2), (1, 3), (1, 4), (2, 5), (5, 6)]);
+        let node = |n: usize| g.find_node(&n).unwrap();
+        let tree = Tree::new(&g, node(0));
+
+        assert_eq!(tree.root(), node(0));
+        assert_eq!(tree.parent(node(6)), Some(node(5)));
+        assert_eq!(tree.parent(node(0)), None);
+        assert_eq!(tree.depth(node(6)), Some(3));
+        assert_eq!(tree.ancestors(node(6)).collect::<Vec<_>>(), vec![node(5), node(2), node(0)]);
+        assert_eq!(tree.ancestor(node(6), 2), Some(node(2)));
+        assert_eq!(tree.ancestor(node(6), 4), None);
+        assert_eq!(tree.subtree_size(node(0)), 7);
+        assert_eq!(tree.subtree_size(node(1)), 3);
+        assert_eq!(tree.subtree_size(node(6)), 1);
+        assert_eq!(tree.lca(node(3), node(4)), Some(node(1)));
+        assert_eq!(tree.lca(node(3), node(6)), Some(node(0)));
+        assert_eq!(tree.lca(node(2), node(6)), Some(node(2)));
+        assert_eq!(tree.distance(node(4), node(6)), Some(5));
+        assert_eq!(tree.distance(node(5), node(5)), Some(0));
+        assert_eq!(tree.total_depth(), 1 + 1 + 2 + 2 + 2 + 3);
+    }
+
+    #[test]
+    fn outside_the_tree() {
+        let g = graph(&[(0, 1), (2, 3)]);
+        let tree = Tree::new(&g, g.find_node(&0).unwrap());
+        let three = g.find_node(&3).unwrap();
+        assert_eq!(tree.depth(three), None);
+        assert_eq!(tree.lca(three, 0), None);
+        assert_eq!(tree.distance(three, 0), None);
+        assert_eq!(tree.subtree_size(three), 0);
+    }
+
+    #[test]
+    fn matches_graph_paths() {
+        // Each node hangs off one picked by a simple LCG, giving an
+        // irregular tree
+        let mut seed: usize = 17;
+        let edges: Vec<(usize, usize)> = (1..2000)
+            .map(|child| {
+                seed = (seed * 1103515245 + 12345) % (1 << 31);
+                (seed % child, child)
+            })
+            .collect();
+        let g = graph(&edges);
+        let root = g.find_node(&0).unwrap();
+        let tree = Tree::new(&g, root);
+
+        // The only path between two nodes of a tree goes through their LCA,
+        // which is the shallowest node on it
+        let from_root = g.bfs(root);
+        for (a, b) in (0..2000).step_by(7).zip((0..2000).rev().step_by(13)) {
+            let (a, b) = (g.find_node(&a).unwrap(), g.find_node(&b).unwrap());
+            let path = g.bfs_in(a, Direction::Both).path_to(b).unwrap();
+            let lca = path.iter().cloned().min_by_key(|&n| from_root.depth(n)).unwrap();
+            assert_eq!(tree.lca(a, b), Some(lca));
+            assert_eq!(tree.distance(a, b), Some(path.len() - 1));
+        }
+    }
+}
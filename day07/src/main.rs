@@ -1,12 +1,18 @@
 use std::fs;
 use std::error::Error;
+use std::time::Duration;
+use intcode::Limits;
 use crate::opcode::Program;
+use crate::pipeline::Graph;
 use itertools::Itertools;
 
+const MAX_INSTRUCTIONS : u64 = 1_000_000;
+const TIMEOUT : Duration = Duration::from_secs(10);
 
 pub mod opcode;
+pub mod pipeline;
 
-fn main() -> Result<(), Box<&'static str>>{
+fn main() -> Result<(), &'static str>{
     let input : Vec<i32> = fs::read_to_string("input.txt")
         .expect("Something went wrong reading the file")
         .split(",")
@@ -15,23 +21,43 @@ fn main() -> Result<(), Box<&'static str>>{
 
     let result = part1(&input);
     eprintln!("part1 = {:#?}", result);
+
+    let result = part2(&input).map_err(|e| e.to_string());
+    eprintln!("part2 = {:#?}", result);
     
      Ok(())   
 }
 
-fn part1(input : &Vec<i32>) -> Result<i32, Box<&str>>{
+fn part1(input : &Vec<i32>) -> Result<i32, &'static str>{
     (0..5).permutations(5)
         .map(|setting| try_combo(input, &setting).expect("invalid combo"))
         .max()
-        .ok_or(Box::new("No max value"))
+        .ok_or("No max value")
+}
+
+/// The biggest signal from amplifiers wired in a feedback loop, each on its own thread.
+fn part2(input : &[i32]) -> Result<i32, Box<dyn Error>> {
+    let mut best = None;
+    for phases in (5..10).permutations(5) {
+        let mut graph = Graph::feedback(input, &phases);
+        graph.set_limits(limits());
+        let signal = graph.run()?.last_output(4).ok_or("No signal")?;
+        best = best.max(Some(signal));
+    }
+    best.ok_or_else(|| "No phase settings".into())
 }
 
+/// Enough for any amplifier, so one that loops forever fails instead.
+fn limits() -> Limits {
+    Limits { max_instructions: Some(MAX_INSTRUCTIONS), timeout: Some(TIMEOUT), ..Limits::default() }
+}
 
 fn try_combo(input : &Vec<i32>, settings: &[i32]) -> Result<i32, Box<dyn Error>> {
     
     let output = settings.iter()
         .fold(0, |output, &phase| {
-            let mut p = Program::new(&input);
+            let mut p = Program::new(input);
+            p.set_limits(limits());
             p.pipe(phase, move || output);
             p.exec().expect("Bad result for program")
         });
@@ -58,6 +84,21 @@ mod tests {
         assert_eq!(result, 54321);
     }
 
+    #[test]
+    fn threaded_series_matches_fold() {
+        let input = vec![3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0];
+        for phases in (0..5).permutations(5) {
+            let threaded = Graph::series(&input, &phases).run().unwrap().last_output(4);
+            assert_eq!(threaded, Some(try_combo(&input, &phases).unwrap()));
+        }
+    }
+
+    #[test]
+    fn feedback_max() {
+        let input = vec![3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5];
+        assert_eq!(part2(&input).unwrap(), 139629729);
+    }
+
     #[test]
     fn test3() {
         let input = vec![3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,00];
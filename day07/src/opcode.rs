@@ -1,14 +1,20 @@
 use std::error::Error;
 use crate::opcode::ParameterMode::{Position, Immediate};
+use intcode::{Breach, Limits, Meter, Snapshot};
 
 type OpCodes = Vec<i32>;
+type InputFn = Box<dyn Fn() -> Option<i32>>;
+type OutputFn = Box<dyn Fn(i32)>;
 
 pub struct Program {
     op_codes : OpCodes,
     p : usize,
     output: i32,
-    input_pipe: (i32, Option<Box<dyn Fn() -> i32>>),
-    read_phase: bool
+    input_pipe: (i32, Option<InputFn>),
+    output_pipe: Option<OutputFn>,
+    read_phase: bool,
+    bad_write: Option<Breach>,
+    meter: Meter
 }
 
 #[derive(PartialEq, Debug)]
@@ -30,51 +36,94 @@ const EQUALS : i32 = 8;
 
 impl Program {
     pub fn new(op_codes: &OpCodes) -> Program {
-        Program {  p: 0, op_codes: op_codes.clone(), output: 0, input_pipe: (0, None), read_phase: false }
+        Program {  p: 0, op_codes: op_codes.clone(), output: 0, input_pipe: (0, None), output_pipe: None, read_phase: false,
+                  bad_write: None, meter: Meter::default() }
     }
     
-     pub fn exec(&mut self) -> Result<i32, Box<dyn Error>> {
-        while self.next_code() != HALT {
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.meter.set_limits(limits);
+    }
+
+    /// How many instructions have run so far.
+    pub fn executed(&self) -> u64 {
+        self.meter.executed()
+    }
 
+     pub fn exec(&mut self) -> Result<i32, Box<dyn Error>> {
+        loop {
             let code = self.next_code();
+            self.meter.check(self.p, code == HALT).map_err(|breach| breach.with(self.snapshot()))?;
+            let pointer = self.p;
             match code {
                 ADD   => self.add(),
                 MUL   => self.mul(),
-                INPUT => { self.input(); } ,
+                INPUT => self.input()?,
                 OUTPUT => self.output(),
                 JUMP_TRUE => self.jump_if_true(),
                 JUMP_FALSE => self.jump_if_false(),
                 LESS_THAN => self.less_than(),
                 EQUALS => self.equals(),
                 HALT  => break,
-                _ => return Err(format!("Unknown OpCode").into())
+                _ => return Err(format!("Unknown OpCode {}", code).into())
+            }
+            if let Some(breach) = self.bad_write.take() {
+                // Report the instruction that tried to write, not the next one
+                self.p = pointer;
+                return Err(breach.with(self.snapshot()).into());
             }
+            self.meter.tick();
         }
 
         Ok(self.output)
     }
     
     pub fn pipe(&mut self, phase: i32, input_supplier: impl Fn() -> i32 + 'static ) {
-        self.input_pipe = (phase, Some(Box::new(input_supplier)));
+        self.input_pipe = (phase, Some(Box::new(move || Some(input_supplier()))));
     }
 
-    fn input(&mut self) {
+    /// Reads every input, phase included, from `input_supplier`. Returning
+    /// None stops the program with an error.
+    pub fn input_from(&mut self, input_supplier: impl Fn() -> Option<i32> + 'static) {
+        self.input_pipe = (0, Some(Box::new(input_supplier)));
+        self.read_phase = true;
+    }
+
+    /// Calls `output_consumer` with every value the program outputs.
+    pub fn output_to(&mut self, output_consumer: impl Fn(i32) + 'static) {
+        self.output_pipe = Some(Box::new(output_consumer));
+    }
+
+    fn input(&mut self) -> Result<(), Box<dyn Error>> {
         let index = self.op_codes[self.p + 1] as usize;
         let (phase, func) = &self.input_pipe;
         let input_value = if self.read_phase {
-           let func = func.as_ref().expect("should be a func");
-            func()
+            let func = func.as_ref().ok_or("No input to read from")?;
+            func().ok_or("Ran out of input")?
         } else {
             self.read_phase = true;
             *phase
         };
-        self.op_codes[index] = input_value;
+        self.write(index, input_value);
         self.p+=2;
+        Ok(())
+    }
+
+    fn write(&mut self, address: usize, value: i32) {
+        match self.meter.check_address(address) {
+            Ok(()) => self.op_codes[address] = value,
+            Err(breach) => self.bad_write = Some(breach)
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot<()> {
+        Snapshot { pointer: self.p, base_offset: 0, executed: self.meter.executed(), state: () }
     }
 
     fn output(&mut self) {
-        self.output = self.op_codes[self.op_codes[self.p + 1] as usize];
-        println!("Ouputting {}",  self.output);
+        self.output = self.param1();
+        if let Some(f) = &self.output_pipe {
+            f(self.output);
+        }
         self.p+=2
     }
 
@@ -113,7 +162,8 @@ impl Program {
     fn binary_op<F>(&mut self, f: F)
         where F: Fn(i32, i32) -> i32 {
         let result_reg = self.op_codes[self.p+3] as usize;
-        self.op_codes[result_reg] = f(self.param1(), self.param2());
+        let value = f(self.param1(), self.param2());
+        self.write(result_reg, value);
         self.p+=4;
     }
 
@@ -152,6 +202,9 @@ impl Program {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::cell::RefCell;
+    use std::convert::TryFrom;
+    use conformance::{Feature, Outcome, run_suite};
 
     #[test]
     fn decode() {
@@ -165,7 +218,54 @@ mod tests {
     #[test]
     fn program() {
         let mut program = Program::new(&vec![1002, 4, 3, 4, 33]);
-        program.exec();
+        assert_eq!(program.exec().unwrap(), 0);
+        assert_eq!(program.op_codes[4], 99);
+    }
+
+    #[test]
+    fn limits() {
+        let mut p = Program::new(&vec![1105, 1, 0]);
+        p.set_limits(Limits { max_instructions: Some(10), ..Limits::default() });
+        let error = p.exec().unwrap_err();
+        assert_eq!(error.to_string(), "Ran more than 10 instructions at 0 after 10 instructions, state ()");
+
+        let mut p = Program::new(&vec![1101, 1, 1, 5, 99, 0]);
+        p.set_limits(Limits { max_address: Some(4), ..Limits::default() });
+        let error = p.exec().unwrap_err();
+        assert_eq!(error.to_string(), "Address 5 is past the limit of 4 at 0 after 0 instructions, state ()");
+        assert_eq!(p.op_codes[5], 0);
+
+        let mut p = Program::new(&vec![1101, 1, 1, 5, 99, 0]);
+        p.set_limits(Limits { max_instructions: Some(1), max_address: Some(5), ..Limits::default() });
+        assert_eq!(p.exec().unwrap(), 0);
+        assert_eq!(p.executed(), 1);
+    }
+
+    /// This VM works in 32 bits and only remembers its last output.
+    #[test]
+    fn conformance() {
+        let report = run_suite("day07 Program", |case| {
+            let op_codes = case.program.iter()
+                .map(|&c| i32::try_from(c).map_err(|_| format!("{} doesn't fit in an i32", c)))
+                .collect::<Result<OpCodes, String>>()?;
+            let mut inputs = case.inputs.iter().map(|&i| i as i32);
+
+            let mut p = Program::new(&op_codes);
+            if let Some(phase) = inputs.next() {
+                let rest = RefCell::new(inputs.collect::<Vec<_>>().into_iter());
+                p.pipe(phase, move || rest.borrow_mut().next().expect("Ran out of input"));
+            }
+            let last_output = p.exec().map_err(|e| e.to_string())?;
+            let memory = p.op_codes.iter().map(|&c| i64::from(c)).collect();
+            Ok(Outcome { outputs: None, last_output: Some(i64::from(last_output)), memory: Some(memory) })
+        });
+        assert_eq!(report.failures(), vec![
+            "day 9 quine",
+            "day 9 sixteen digit",
+            "day 9 large number",
+            "day 9 relative input"
+        ]);
+        report.assert_supports(&[Feature::AddMul, Feature::Io, Feature::ParameterModes, Feature::Compare, Feature::Jump]);
     }
 }
 
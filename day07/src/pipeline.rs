@@ -0,0 +1,364 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use intcode::Limits;
+use crate::opcode::Program;
+
+type OpCodes = Vec<i32>;
+
+#[derive(Debug, PartialEq)]
+pub enum PipelineError {
+    /// Every machine still running was waiting on input nobody would send.
+    Deadlock { waiting: Vec<usize> },
+    Machine { index: usize, message: String }
+}
+
+impl Display for PipelineError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PipelineError::Deadlock { waiting } => write!(f, "Deadlock, machines {:?} are all waiting on input", waiting),
+            PipelineError::Machine { index, message } => write!(f, "Machine {} failed: {}", index, message)
+        }
+    }
+}
+
+impl Error for PipelineError {}
+
+/// Which machines are waiting on input, shared by every machine so the
+/// last one to block can tell that nothing will ever wake them.
+struct Board {
+    waiting: Vec<bool>,
+    halted: Vec<bool>,
+    starved: Vec<bool>,
+    deadlocked: bool
+}
+
+impl Board {
+    fn all_stuck(&self) -> bool {
+        self.waiting.iter().any(|&w| w)
+            && self.waiting.iter().zip(&self.halted).all(|(&waiting, &halted)| waiting || halted)
+    }
+}
+
+struct Shared {
+    board: Mutex<Board>,
+    wake: Condvar
+}
+
+impl Shared {
+    /// Blocks until a value arrives for `index`, or None on deadlock.
+    fn receive(&self, index: usize, receiver: &Receiver<i32>) -> Option<i32> {
+        let mut board = self.board.lock().unwrap();
+        loop {
+            if let Ok(value) = receiver.try_recv() {
+                board.waiting[index] = false;
+                return Some(value);
+            }
+            if board.deadlocked {
+                board.starved[index] = true;
+                return None;
+            }
+            board.waiting[index] = true;
+            if board.all_stuck() {
+                board.deadlocked = true;
+                board.starved[index] = true;
+                self.wake.notify_all();
+                return None;
+            }
+            board = self.wake.wait(board).unwrap();
+        }
+    }
+
+    fn send(&self, value: i32, outputs: &[(usize, Sender<i32>)]) {
+        let mut board = self.board.lock().unwrap();
+        for (to, sender) in outputs {
+            // A machine that has already halted just never reads it
+            if sender.send(value).is_ok() {
+                board.waiting[*to] = false;
+            }
+        }
+        self.wake.notify_all();
+    }
+
+    fn halt(&self, index: usize) {
+        // Also called while a panicking machine unwinds, so don't panic again
+        let mut board = self.board.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        board.halted[index] = true;
+        board.waiting[index] = false;
+        if board.all_stuck() {
+            board.deadlocked = true;
+        }
+        self.wake.notify_all();
+    }
+}
+
+/// Marks a machine halted however its thread ends, so one that panics
+/// doesn't leave the others waiting on it forever.
+struct HaltOnDrop<'a> {
+    index: usize,
+    shared: &'a Shared
+}
+
+impl Drop for HaltOnDrop<'_> {
+    fn drop(&mut self) {
+        self.shared.halt(self.index);
+    }
+}
+
+struct Node {
+    op_codes: OpCodes,
+    inputs: Vec<i32>,
+    outputs: Vec<usize>
+}
+
+/// Intcode machines wired output to input, each running on its own thread.
+#[derive(Default)]
+pub struct Graph {
+    nodes: Vec<Node>,
+    limits: Limits
+}
+
+/// Everything each machine output, in the order the machines were added.
+#[derive(Debug, PartialEq)]
+pub struct Run {
+    pub outputs: Vec<Vec<i32>>
+}
+
+impl Run {
+    pub fn last_output(&self, machine: usize) -> Option<i32> {
+        self.outputs.get(machine)?.last().cloned()
+    }
+}
+
+impl Graph {
+    pub fn new() -> Graph {
+        Graph::default()
+    }
+
+    /// Adds a machine, returning its index.
+    pub fn add(&mut self, op_codes: &[i32]) -> usize {
+        self.nodes.push(Node { op_codes: op_codes.to_vec(), inputs: vec![], outputs: vec![] });
+        self.nodes.len() - 1
+    }
+
+    /// Queues a value for a machine to read before anything sent to it.
+    pub fn feed(&mut self, machine: usize, value: i32) {
+        self.nodes[machine].inputs.push(value);
+    }
+
+    /// Sends everything `from` outputs to `to`.
+    pub fn connect(&mut self, from: usize, to: usize) {
+        self.nodes[from].outputs.push(to);
+    }
+
+    /// Holds every machine to `limits`.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Amplifiers one after the other, the first given a 0 to start.
+    pub fn series(op_codes: &[i32], phases: &[i32]) -> Graph {
+        let mut graph = Graph::new();
+        for (i, &phase) in phases.iter().enumerate() {
+            let amp = graph.add(op_codes);
+            graph.feed(amp, phase);
+            if i > 0 {
+                graph.connect(amp - 1, amp);
+            }
+        }
+        if !phases.is_empty() {
+            graph.feed(0, 0);
+        }
+        graph
+    }
+
+    /// Amplifiers in series with the last one feeding back into the first.
+    pub fn feedback(op_codes: &[i32], phases: &[i32]) -> Graph {
+        let mut graph = Graph::series(op_codes, phases);
+        if let Some(last) = phases.len().checked_sub(1) {
+            graph.connect(last, 0);
+        }
+        graph
+    }
+
+    pub fn run(self) -> Result<Run, PipelineError> {
+        let size = self.nodes.len();
+        let shared = Arc::new(Shared {
+            board: Mutex::new(Board {
+                waiting: vec![false; size],
+                halted: vec![false; size],
+                starved: vec![false; size],
+                deadlocked: false
+            }),
+            wake: Condvar::new()
+        });
+
+        let (senders, receivers): (Vec<Sender<i32>>, Vec<Receiver<i32>>) = (0..size).map(|_| channel()).unzip();
+        for (node, sender) in self.nodes.iter().zip(&senders) {
+            for &value in &node.inputs {
+                sender.send(value).unwrap();
+            }
+        }
+
+        let limits = self.limits;
+        let handles: Vec<_> = self.nodes.into_iter()
+            .zip(receivers)
+            .enumerate()
+            .map(|(index, (node, receiver))| {
+                let outputs: Vec<(usize, Sender<i32>)> = node.outputs.iter().map(|&to| (to, senders[to].clone())).collect();
+                let shared = Arc::clone(&shared);
+                let limits = limits.clone();
+                thread::spawn(move || run_machine(index, &node.op_codes, limits, receiver, outputs, &shared))
+            })
+            .collect();
+        drop(senders);
+
+        let results: Vec<_> = handles.into_iter()
+            .enumerate()
+            .map(|(index, handle)| handle.join()
+                .unwrap_or_else(|_| Err(PipelineError::Machine { index, message: "panicked".to_string() })))
+            .collect();
+
+        // A machine that broke on its own is the real problem, even if the
+        // rest then deadlocked waiting on it
+        let board = shared.board.lock().unwrap();
+        let mut outputs = vec![];
+        for (index, result) in results.into_iter().enumerate() {
+            match result {
+                Ok(produced) => outputs.push(produced),
+                Err(e) if !board.starved[index] => return Err(e),
+                Err(_) => {}
+            }
+        }
+        if board.deadlocked {
+            let waiting = (0..size).filter(|&i| board.starved[i]).collect();
+            return Err(PipelineError::Deadlock { waiting });
+        }
+        Ok(Run { outputs })
+    }
+}
+
+fn run_machine(index: usize, op_codes: &[i32], limits: Limits, receiver: Receiver<i32>,
+               outputs: Vec<(usize, Sender<i32>)>, shared: &Arc<Shared>) -> Result<Vec<i32>, PipelineError> {
+    let produced = Rc::new(RefCell::new(vec![]));
+    let mut program = Program::new(&op_codes.to_vec());
+    program.set_limits(limits);
+
+    let input_shared = Arc::clone(shared);
+    program.input_from(move || input_shared.receive(index, &receiver));
+
+    let output_shared = Arc::clone(shared);
+    let recorded = Rc::clone(&produced);
+    program.output_to(move |value| {
+        recorded.borrow_mut().push(value);
+        output_shared.send(value, &outputs);
+    });
+
+    let halt = HaltOnDrop { index, shared };
+    let result = program.exec();
+    drop(halt);
+    result.map_err(|e| PipelineError::Machine { index, message: e.to_string() })?;
+
+    let produced = produced.borrow().clone();
+    Ok(produced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn series() {
+        let input = vec![3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0];
+        let run = Graph::series(&input, &[4, 3, 2, 1, 0]).run().unwrap();
+        assert_eq!(run.last_output(4), Some(43210));
+        assert_eq!(run.outputs[0], vec![4]);
+    }
+
+    #[test]
+    fn feedback() {
+        let input = vec![3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5];
+        let run = Graph::feedback(&input, &[9, 8, 7, 6, 5]).run().unwrap();
+        assert_eq!(run.last_output(4), Some(139629729));
+
+        let input = vec![3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,
+                         -5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,
+                         53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10];
+        let run = Graph::feedback(&input, &[9, 7, 8, 5, 6]).run().unwrap();
+        assert_eq!(run.last_output(4), Some(18216));
+    }
+
+    #[test]
+    fn fan_out() {
+        // Doubles its input
+        let double = vec![3, 9, 1002, 9, 2, 9, 4, 9, 99, 0];
+        let mut graph = Graph::new();
+        let source = graph.add(&double);
+        let left = graph.add(&double);
+        let right = graph.add(&double);
+        graph.feed(source, 5);
+        graph.connect(source, left);
+        graph.connect(source, right);
+        let run = graph.run().unwrap();
+        assert_eq!(run.outputs, vec![vec![10], vec![20], vec![20]]);
+    }
+
+    #[test]
+    fn detects_deadlock() {
+        // Both machines wait for the other to speak first
+        let echo = vec![3, 0, 4, 0, 99];
+        let mut graph = Graph::new();
+        let a = graph.add(&echo);
+        let b = graph.add(&echo);
+        graph.connect(a, b);
+        graph.connect(b, a);
+        assert_eq!(graph.run(), Err(PipelineError::Deadlock { waiting: vec![0, 1] }));
+
+        // Waiting on a machine that has already halted
+        let mut graph = Graph::new();
+        let a = graph.add(&[99]);
+        let b = graph.add(&echo);
+        graph.connect(a, b);
+        assert_eq!(graph.run(), Err(PipelineError::Deadlock { waiting: vec![1] }));
+    }
+
+    #[test]
+    fn machine_errors() {
+        let broken = Err(PipelineError::Machine { index: 0, message: "Unknown OpCode 42".to_string() });
+        let mut graph = Graph::new();
+        graph.add(&[104, 1, 42]);
+        assert_eq!(graph.run(), broken);
+
+        // The machine waiting on it deadlocks, but the error is the cause
+        let mut graph = Graph::new();
+        let a = graph.add(&[104, 1, 42]);
+        let b = graph.add(&[3, 0, 3, 0, 99]);
+        graph.connect(a, b);
+        assert_eq!(graph.run(), broken);
+    }
+
+    #[test]
+    fn machine_panics() {
+        // Writes past the end of its memory, with b waiting on its output
+        let mut graph = Graph::new();
+        let a = graph.add(&[1101, 1, 1, 1000, 99]);
+        let b = graph.add(&[3, 0, 99]);
+        graph.connect(a, b);
+        assert_eq!(graph.run(), Err(PipelineError::Machine { index: 0, message: "panicked".to_string() }));
+    }
+
+    #[test]
+    fn runaway_machine() {
+        let mut graph = Graph::new();
+        let a = graph.add(&[104, 1, 1105, 1, 0]);
+        let b = graph.add(&[3, 0, 4, 0, 99]);
+        graph.connect(a, b);
+        graph.set_limits(Limits { max_instructions: Some(100), ..Limits::default() });
+        let message = "Ran more than 100 instructions at 0 after 100 instructions, state ()".to_string();
+        assert_eq!(graph.run(), Err(PipelineError::Machine { index: 0, message }));
+    }
+}
@@ -1,8 +1,13 @@
 use std::fs;
+use std::time::Duration;
+use intcode::Limits;
 use crate::opcode::Program;
 
 pub mod opcode;
 
+const MAX_INSTRUCTIONS : u64 = 10_000_000;
+const TIMEOUT : Duration = Duration::from_secs(30);
+
 fn main() {
     let input : Vec<i64> = fs::read_to_string("input.txt")
         .expect("Something went wrong reading the file")
@@ -11,6 +16,7 @@ fn main() {
         .collect();
     
     let mut p = Program::new(&input);
-    p.exec();
+    p.set_limits(Limits { max_instructions: Some(MAX_INSTRUCTIONS), timeout: Some(TIMEOUT), ..Limits::default() });
+    p.exec().expect("Program failed");
 }
     
\ No newline at end of file
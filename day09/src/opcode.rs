@@ -1,16 +1,18 @@
 use std::error::Error;
 use crate::opcode::ParameterMode::{Position, Immediate, Relative};
 use std::collections::HashMap;
+use intcode::{Breach, Limits, Meter, Snapshot};
 
 type OpCodes = Vec<i64>;
 
 pub struct Program {
-    op_codes : OpCodes,
     p : usize,
     output: i64,
     input: i64, 
     base_offset: i64,
-    memory: HashMap<usize, i64>
+    memory: HashMap<usize, i64>,
+    bad_write: Option<Breach>,
+    meter: Meter
 }
 
 #[derive(PartialEq, Debug)]
@@ -45,7 +47,6 @@ const ADJUST_BASE_OFFSET : i64 = 9;
 
 impl Program {
     pub fn new(op_codes: &OpCodes) -> Program {
-        let op_codes = op_codes.clone();
         let memory : HashMap<usize,i64> = op_codes.iter()
             .enumerate()
             .fold(HashMap::new(), |acc, (i, op_code)| {
@@ -54,12 +55,22 @@ impl Program {
                 acc
             });
         
-        Program {  p: 0, op_codes, output: 0, input:2,  base_offset: 0, memory }
+        Program {  p: 0, output: 0, input:2,  base_offset: 0, memory, bad_write: None, meter: Meter::default() }
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.meter.set_limits(limits);
+    }
+
+    /// How many instructions have run so far.
+    pub fn executed(&self) -> u64 {
+        self.meter.executed()
     }
     
      pub fn exec(&mut self) -> Result<i64, Box<dyn Error>> {
         while self.next_code() != HALT {
-
+            self.meter.check(self.p, false).map_err(|breach| breach.with(self.snapshot()))?;
+            let pointer = self.p;
             let code = self.next_code();
             match code {
                 ADD   => self.add(),
@@ -72,13 +83,23 @@ impl Program {
                 EQUALS => self.equals(),
                 ADJUST_BASE_OFFSET => self.adj_base_offset(),
                 HALT  => break,
-                _ => return Err(format!("Unknown OpCode").into())
+                _ => return Err(format!("Unknown OpCode {}", code).into())
             }
+            if let Some(breach) = self.bad_write.take() {
+                // Report the instruction that tried to write, not the next one
+                self.p = pointer;
+                return Err(breach.with(self.snapshot()).into());
+            }
+            self.meter.tick();
         }
 
         Ok(self.output)
     }
     
+    fn snapshot(&self) -> Snapshot<()> {
+        Snapshot { pointer: self.p, base_offset: self.base_offset, executed: self.meter.executed(), state: () }
+    }
+
     fn input(&mut self) {
         println!("input");
         self.update_param(self.input, 1);
@@ -95,11 +116,15 @@ impl Program {
         };
         let index = self.p + param;
         let literal_value = *self.memory.get(&index).unwrap_or(&0);
-        match param_mode {
-            Position => self.memory.insert(literal_value as usize, value),
-            Relative => self.memory.insert((self.base_offset + literal_value) as usize, value),
+        let address = match param_mode {
+            Position => literal_value as usize,
+            Relative => (self.base_offset + literal_value) as usize,
             _ => panic!("Problem updating param1, unknown param mode")
         };
+        match self.meter.check_address(address) {
+            Ok(()) => { self.memory.insert(address, value); },
+            Err(breach) => self.bad_write = Some(breach)
+        }
     }
 
     fn output(&mut self) {
@@ -195,7 +220,7 @@ impl Program {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::opcode::ParameterMode::Relative;
+    use conformance::{Feature, Outcome, run_suite};
 
     #[test]
     fn decode() {
@@ -209,7 +234,7 @@ mod tests {
     #[test]
     fn program() {
         let mut program = Program::new(&vec![1002, 4, 3, 4, 33]);
-        program.exec();
+        assert_eq!(program.exec().unwrap(), 0);
     }
     
     #[test]
@@ -227,23 +252,66 @@ mod tests {
     fn test_base_pointer() {
         let input = vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
         let mut p = Program::new(&input);
-        p.exec();
+        assert_eq!(p.exec().unwrap(), 99);
     }
     
     #[test]
     fn sixteen_digit() {
         let input = vec![1102,34915192,34915192,7,4,7,99,0];
         let mut p = Program::new(&input);
-        p.exec();
+        assert_eq!(p.exec().unwrap(), 1219070632396864);
     }
     
     #[test]
     fn number() {
-        
         let input = vec![104,1125899906842624,99];
         let mut p = Program::new(&input);
-        p.exec();
-        
+        assert_eq!(p.exec().unwrap(), 1125899906842624);
+    }
+
+    #[test]
+    fn limits() {
+        let mut p = Program::new(&vec![1105, 1, 0]);
+        p.set_limits(Limits { max_instructions: Some(10), ..Limits::default() });
+        let error = p.exec().unwrap_err();
+        assert_eq!(error.to_string(), "Ran more than 10 instructions at 0 after 10 instructions, state ()");
+
+        let mut p = Program::new(&vec![104, 1, 1101, 1, 1, 200, 99]);
+        p.set_limits(Limits { max_address: Some(100), ..Limits::default() });
+        let error = p.exec().unwrap_err();
+        assert_eq!(error.to_string(), "Address 200 is past the limit of 100 at 2 after 1 instructions, state ()");
+
+        let mut p = Program::new(&vec![1101, 1, 1, 5, 99, 0]);
+        p.set_limits(Limits { max_instructions: Some(1), max_address: Some(5), ..Limits::default() });
+        p.exec().unwrap();
+        assert_eq!(p.executed(), 1);
+    }
+
+    /// This VM always reads 2 as input and only remembers its last output.
+    #[test]
+    fn conformance() {
+        let report = run_suite("day09 Program", |case| {
+            let mut p = Program::new(&case.program.to_vec());
+            let last_output = p.exec().map_err(|e| e.to_string())?;
+            Ok(Outcome { outputs: None, last_output: Some(last_output), memory: None })
+        });
+        assert_eq!(report.failures(), vec![
+            "day 5 echo",
+            "day 5 equal to 8 (position)",
+            "day 5 not less than 8 (position)",
+            "day 5 equal to 8 (immediate)",
+            "day 5 not less than 8 (immediate)",
+            "day 5 zero jump (position)",
+            "day 5 zero jump (immediate)",
+            "day 5 exactly 8",
+            "day 5 above 8",
+            "day 7 amplifier 43210",
+            "day 7 amplifier 54321",
+            "day 7 amplifier 65210",
+            "day 9 relative input"
+        ]);
+        report.assert_supports(&[Feature::AddMul, Feature::ParameterModes, Feature::Compare, Feature::Jump,
+            Feature::Relative, Feature::ExtraMemory, Feature::LargeNumbers]);
     }
 }
 
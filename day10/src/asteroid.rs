@@ -1,25 +1,62 @@
 use std::str::FromStr;
 use std::fmt::{Display, Formatter, Error};
 use itertools::Itertools;
-use std::collections::HashMap;
 
 type Coord = (usize, usize);
 
 pub struct AsteroidField {
     field: Vec<Vec<MonitoringStation>>,
     width: usize,
-    height: usize
+    height: usize,
+    station: Option<Coord>
 }
 
+/// Why a map couldn't be read. Lines and columns count from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldError {
+    Empty,
+    Ragged { line: usize, expected: usize, found: usize },
+    UnknownSymbol { line: usize, column: usize, symbol: char },
+    /// An X on `line` when there was already one at `first`.
+    SecondStation { line: usize, column: usize, first: Coord }
+}
+
+impl Display for FieldError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            FieldError::Empty => write!(f, "the map is empty"),
+            FieldError::Ragged { line, expected, found } =>
+                write!(f, "line {} is {} wide but the map is {} wide", line, found, expected),
+            FieldError::UnknownSymbol { line, column, symbol } =>
+                write!(f, "line {}, column {}: unknown symbol {:?}", line, column, symbol),
+            FieldError::SecondStation { line, column, first: (x, y) } =>
+                write!(f, "line {}, column {}: second station, the first is at {},{}", line, column, x, y)
+        }
+    }
+}
+
+impl std::error::Error for FieldError {}
+
 pub struct MonitoringStation {
-    coord: Coord,
-    has_asteroid: bool,
-    peers: Vec<Path>
+    has_asteroid: bool
+}
+
+/// What to draw over the field. `counts` replaces each asteroid with how
+/// many others it can see, and `station` is marked with an X.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Overlay {
+    pub counts: bool,
+    pub station: Option<Coord>
+}
+
+/// The field drawn with an overlay, made by `AsteroidField::render`.
+pub struct Rendering<'a> {
+    field: &'a AsteroidField,
+    overlay: Overlay
 }
 
 #[derive(Copy, Clone)]
 pub struct Path {
-    length: f32,
     start: Coord,
     end: Coord
 }
@@ -27,8 +64,7 @@ pub struct Path {
 impl Path {
     
     pub fn new(start: Coord, end: Coord) -> Path {
-        let length = Self::length(start, end);
-        Path { start, end, length }
+        Path { start, end }
     }
     
     fn degrees(&self) -> f32 {
@@ -48,26 +84,41 @@ impl Path {
 
 
 impl AsteroidField {
+
+    /// Where an X in the map put the station, if there was one.
+    pub fn station(&self) -> Option<Coord> {
+        self.station
+    }
     
-    pub fn calc_best(&mut self) -> (Coord, usize) {
-        let mut bestest = vec![];
-        for y in 0..self.height {
-            for x in 0..self.width {
-                if self.field[y][x].has_asteroid {
-                    let visible = self.visible_asteroid((x, y));
-                    bestest.push((visible, (x,y)));
-                }
-            }
-        }
+    pub fn calc_best(&self) -> (Coord, usize) {
+        let bestest = self.visibility_counts().into_iter()
+            .enumerate()
+            .flat_map(|(y, row)| row.into_iter()
+                .enumerate()
+                .filter_map(move |(x, visible)| visible.map(|visible| (visible, (x, y)))));
 
-        let (amount, winner) = bestest.iter()
-            .max_by_key(|(a, _)| a)
+        let (amount, winner) = bestest
+            .max_by_key(|(a, _)| *a)
             .expect("No max found");
 
-        (*winner, *amount)
+        (winner, amount)
+    }
+
+    /// How many asteroids each asteroid can see, indexed `[y][x]`, with
+    /// None where there is no asteroid.
+    pub fn visibility_counts(&self) -> Vec<Vec<Option<usize>>> {
+        (0..self.height)
+            .map(|y| (0..self.width)
+                .map(|x| if self.field[y][x].has_asteroid { Some(self.visible_asteroid((x, y))) } else { None })
+                .collect())
+            .collect()
+    }
+
+    pub fn render(&self, overlay: Overlay) -> Rendering<'_> {
+        Rendering { field: self, overlay }
     }
     
-    pub fn visible_asteroid(&mut self, coord: Coord) -> usize {
+    pub fn visible_asteroid(&self, coord: Coord) -> usize {
         let mut visible: Vec<Path> = vec![];
         for y in 0..self.height {
             for x in 0..self.width {
@@ -90,6 +141,91 @@ impl AsteroidField {
 
 
 
+/// '#' is an asteroid and '.' empty space. As in the puzzle's examples,
+/// digits are asteroids too, and an X is the station, built on an asteroid.
+impl FromStr for AsteroidField {
+    type Err = FieldError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<&str> = s.trim_end().lines().map(str::trim_end).collect();
+        let width = lines.first().map_or(0, |line| line.chars().count());
+        if width == 0 {
+            return Err(FieldError::Empty);
+        }
+
+        let mut station = None;
+        let mut field = vec![];
+        for (y, line) in lines.iter().enumerate() {
+            let found = line.chars().count();
+            if found != width {
+                return Err(FieldError::Ragged { line: y + 1, expected: width, found });
+            }
+            let row = line.chars()
+                .enumerate()
+                .map(|(x, c)| {
+                    if c == 'X' {
+                        if let Some(first) = station {
+                            return Err(FieldError::SecondStation { line: y + 1, column: x + 1, first });
+                        }
+                        station = Some((x, y));
+                    }
+                    to_monitoring_station(c).ok_or(FieldError::UnknownSymbol { line: y + 1, column: x + 1, symbol: c })
+                })
+                .collect::<Result<Vec<MonitoringStation>, FieldError>>()?;
+            field.push(row);
+        }
+
+        let height = field.len();
+        Ok(AsteroidField { field, width, height, station })
+    }
+}
+
+fn to_monitoring_station(c: char) -> Option<MonitoringStation> {
+    let has_asteroid = match c {
+        '#' | 'X' | '0'..='9' => true,
+        '.' => false,
+        _ => return None
+    };
+    Some(MonitoringStation { has_asteroid })
+}
+
+impl Display for AsteroidField {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}", self.render(Overlay { station: self.station, ..Overlay::default() }))
+    }
+}
+
+/// Counts wider than one digit get cells padded to the widest count, with
+/// a space between them.
+impl Display for Rendering<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let field = self.field;
+        let counts = if self.overlay.counts { Some(field.visibility_counts()) } else { None };
+        let cell_width = counts.iter().flatten().flatten().flatten().max().map_or(1, |max| max.to_string().len());
+        let separator = if cell_width > 1 { " " } else { "" };
+
+        for y in 0..field.height {
+            let cells: Vec<String> = (0..field.width)
+                .map(|x| {
+                    let count = counts.as_ref().and_then(|counts| counts[y][x]);
+                    if self.overlay.station == Some((x, y)) {
+                        "X".to_string()
+                    } else if let Some(count) = count {
+                        count.to_string()
+                    } else if field.field[y][x].has_asteroid {
+                        "#".to_string()
+                    } else {
+                        ".".to_string()
+                    }
+                })
+                .map(|cell| format!("{:>width$}", cell, width = cell_width))
+                .collect();
+            writeln!(f, "{}", cells.join(separator))?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -126,7 +262,7 @@ mod tests {
 #####
 ....#
 ...##";
-        let mut asteroid_field : AsteroidField = input.parse().unwrap();
+        let asteroid_field : AsteroidField = input.parse().unwrap();
         println!("{}", asteroid_field);
         let peers = asteroid_field.visible_asteroid((3, 4));
 
@@ -150,7 +286,7 @@ mod tests {
 ##...#..#.
 .#....####";
 
-        let mut asteroid_field : AsteroidField = input.parse().unwrap();
+        let asteroid_field : AsteroidField = input.parse().unwrap();
         let (winner, amount) = asteroid_field.calc_best();
         assert_eq!(winner, (5, 8));
         assert_eq!(amount, 33);
@@ -169,7 +305,7 @@ mod tests {
 ......#...
 .####.###.";
 
-        let mut asteroid_field : AsteroidField = input.parse().unwrap();
+        let asteroid_field : AsteroidField = input.parse().unwrap();
         let (winner, amount) = asteroid_field.calc_best();
         assert_eq!(winner, (1,2));
         assert_eq!(amount, 35);
@@ -188,7 +324,7 @@ mod tests {
 .##...##.#
 .....#.#..";
 
-        let mut asteroid_field : AsteroidField = input.parse().unwrap();
+        let asteroid_field : AsteroidField = input.parse().unwrap();
         let (winner, amount) = asteroid_field.calc_best();
         assert_eq!(winner, (6,3));
         assert_eq!(amount, 41);
@@ -196,51 +332,68 @@ mod tests {
     
     #[test]
     fn example5() {
-        let input = std::fs::read_to_string("example5.txt").unwrap();let mut asteroid_field : AsteroidField = input.parse().unwrap();
+        let input = std::fs::read_to_string("example5.txt").unwrap();let asteroid_field : AsteroidField = input.parse().unwrap();
         let (winner, amount) = asteroid_field.calc_best();
         assert_eq!(winner, (11,13));
         assert_eq!(amount, 210);
         
     }
-}
 
-impl FromStr for AsteroidField {
-    type Err = ();
+    #[test]
+    fn heat_map() {
+        let input = ".#..#
+.....
+#####
+....#
+...##";
+        let asteroid_field : AsteroidField = input.parse().unwrap();
+        let counts = Overlay { counts: true, station: None };
+        assert_eq!(asteroid_field.render(counts).to_string(), ".7..7
+.....
+67775
+....7
+...87
+");
+        let (winner, _) = asteroid_field.calc_best();
+        let station = Overlay { counts: true, station: Some(winner) };
+        assert!(asteroid_field.render(station).to_string().ends_with("...X7\n"));
+        assert_eq!(asteroid_field.to_string(), input.to_string() + "\n");
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let field = s.lines().enumerate()
-            .map(to_stations)
-            .collect::<Vec<Vec<MonitoringStation>>>();
-        
-        let height = field.len();
-        let width = field[0].len();
-        
-        Ok(AsteroidField { field, width, height})
+    #[test]
+    fn wide_heat_map() {
+        let input = std::fs::read_to_string("example5.txt").unwrap();
+        let asteroid_field : AsteroidField = input.parse().unwrap();
+        let rendering = asteroid_field.render(Overlay { counts: true, station: Some((11, 13)) }).to_string();
+        let row = rendering.lines().nth(13).unwrap();
+        assert_eq!(&row[11 * 4..11 * 4 + 3], "  X");
+        assert_eq!(rendering.lines().next().unwrap().len(), 20 * 3 + 19);
     }
-}
 
-fn to_stations(y_line: (usize, &str)) -> Vec<MonitoringStation>{
-    y_line.1.chars()
-        .enumerate()
-        .map(|(x, c)| to_monitoring_station((x, y_line.0), c))
-        .collect()
-}
+    #[test]
+    fn station_marker() {
+        let input = ".#....#####...#..
+##...##.#####..##
+##...#...#.#####.
+..#.....X...###..
+..#.#.....#....##";
+        let asteroid_field : AsteroidField = input.parse().unwrap();
+        assert_eq!(asteroid_field.station(), Some((8, 3)));
+        assert_eq!(asteroid_field.to_string(), input.to_string() + "\n");
 
-fn to_monitoring_station(coord: Coord, c: char) -> MonitoringStation {
-    let has_asteroid = c == '#';
-    MonitoringStation { coord, has_asteroid, peers: vec![] }
-}
+        let numbered : AsteroidField = ".7..7\n.....\n67775\n....7\n...87\n\n".parse().unwrap();
+        assert_eq!(numbered.calc_best(), ((3, 4), 8));
+        assert_eq!(numbered.station(), None);
+    }
 
-impl Display for AsteroidField {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        for y in 0..self.height {
-            for x in 0..self.width {
-                let c = if self.field[y][x].has_asteroid { "#" } else { "." };
-                write!(f, "{}", c);
-            }
-            writeln!(f);
-        }
-        Ok(())
+    #[test]
+    fn invalid_maps() {
+        let error = |input: &str| input.parse::<AsteroidField>().err().unwrap();
+        assert_eq!(error(""), FieldError::Empty);
+        assert_eq!(error("\n\n"), FieldError::Empty);
+        assert_eq!(error("#..\n#.\n"), FieldError::Ragged { line: 2, expected: 3, found: 2 });
+        assert_eq!(error("#..\n.*."), FieldError::UnknownSymbol { line: 2, column: 2, symbol: '*' });
+        assert_eq!(error("X..\n..X"), FieldError::SecondStation { line: 2, column: 3, first: (0, 0) });
+        assert_eq!(error("X..\n..X").to_string(), "line 2, column 3: second station, the first is at 0,0");
     }
 }
-
@@ -1,10 +1,19 @@
-use crate::asteroid::AsteroidField;
+use crate::asteroid::{AsteroidField, Overlay};
 
 pub mod asteroid;
 
+/// An X in the map fixes where the station goes, otherwise the best spot is
+/// found. `--counts` also prints how many asteroids each one can see, with
+/// the station marked X.
 fn main() {
     let input = std::fs::read_to_string("input.txt").unwrap();
-    let mut asteroid_field : AsteroidField = input.parse().unwrap();
-    let (winner, amount) = asteroid_field.calc_best();
+    let asteroid_field : AsteroidField = input.parse().unwrap_or_else(|e| panic!("Bad map: {}", e));
+    let (winner, amount) = match asteroid_field.station() {
+        Some(station) => (station, asteroid_field.visible_asteroid(station)),
+        None => asteroid_field.calc_best()
+    };
+    if std::env::args().any(|arg| arg == "--counts") {
+        print!("{}", asteroid_field.render(Overlay { counts: true, station: Some(winner) }));
+    }
     eprintln!("winner = {:#?} with {}", winner, amount);
 }
@@ -1,5 +1,6 @@
-#![feature(entry_insert)]
 use std::fs;
+use std::time::Duration;
+use intcode::Limits;
 use crate::opcode::Program;
 use std::collections::HashMap;
 use crate::Direction::{North, South, West, East};
@@ -9,6 +10,9 @@ use itertools::Itertools;
 
 type Coord = (i32, i32);
 
+const MAX_INSTRUCTIONS : u64 = 10_000_000;
+const TIMEOUT : Duration = Duration::from_secs(30);
+
 pub mod opcode;
 
 #[derive(Debug,Clone)]
@@ -57,7 +61,7 @@ fn main() {
         let mut state = state.clone();
         let position = state.position;
         if state.count % 2 == 0 {
-            state.canvas.entry(state.position).insert(output);
+            state.canvas.insert(state.position, output);
             state.seen.push(state.position);
 
         } else {
@@ -79,6 +83,7 @@ fn main() {
         state
     });
 
+    p.set_limits(Limits { max_instructions: Some(MAX_INSTRUCTIONS), timeout: Some(TIMEOUT), ..Limits::default() });
     let final_state = p.exec().expect("bad");
 
     eprintln!("unique locations painted = {}", final_state.seen.iter().unique().count());
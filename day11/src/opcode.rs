@@ -2,17 +2,21 @@ use std::error::Error;
 use crate::opcode::ParameterMode::{Position, Immediate, Relative};
 use std::collections::HashMap;
 use std::fmt::Debug;
+use intcode::{Breach, Limits, Meter, Snapshot};
 
 type OpCodes = Vec<i64>;
+type InputFn<S> = Box<dyn Fn(&S) -> i64>;
+type OutputFn<S> = Box<dyn Fn(&S, i64) -> S>;
 
 pub struct Program<S> {
-    op_codes : OpCodes,
     p : usize,
     base_offset: i64,
     memory: HashMap<usize, i64>,
+    bad_write: Option<Breach>,
+    meter: Meter,
     state: S,
-    input_fn: Option<Box<dyn Fn(&S) -> i64>>,
-    output_fn: Option<Box<dyn Fn(&S, i64) -> S>>,
+    input_fn: Option<InputFn<S>>,
+    output_fn: Option<OutputFn<S>>,
 }
 
 #[derive(PartialEq, Debug)]
@@ -45,9 +49,8 @@ const EQUALS : i64 = 8;
 const ADJUST_BASE_OFFSET : i64 = 9;
 
 
-impl <S: Default + Debug> Program<S> {
+impl <S: Default + Debug + Clone + 'static> Program<S> {
     pub fn new(op_codes: &OpCodes) -> Program<S> {
-        let op_codes = op_codes.clone();
         let memory : HashMap<usize,i64> = op_codes.iter()
             .enumerate()
             .fold(HashMap::new(), |acc, (i, op_code)| {
@@ -60,8 +63,9 @@ impl <S: Default + Debug> Program<S> {
 
         Program {
             p: 0,
-            op_codes,
             memory,
+            bad_write: None,
+            meter: Meter::default(),
             state,
             base_offset: 0,
             input_fn: None,
@@ -69,6 +73,20 @@ impl <S: Default + Debug> Program<S> {
         }
     }
 
+    /// The value at `address`, which is 0 for memory the program never touched.
+    pub fn peek(&self, address: usize) -> i64 {
+        *self.memory.get(&address).unwrap_or(&0)
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.meter.set_limits(limits);
+    }
+
+    /// How many instructions have run so far.
+    pub fn executed(&self) -> u64 {
+        self.meter.executed()
+    }
+
     pub fn set_input_fn<F>(&mut self, f: F )
         where F: Fn(&S) -> i64 + 'static {
         self.input_fn = Some(Box::new(f));
@@ -81,7 +99,8 @@ impl <S: Default + Debug> Program<S> {
     
      pub fn exec(&mut self) -> Result<&S, Box<dyn Error>> {
         while self.next_code() != HALT {
-
+            self.meter.check(self.p, false).map_err(|breach| breach.with(self.snapshot()))?;
+            let pointer = self.p;
             let code = self.next_code();
             match code {
                 ADD   => self.add(),
@@ -94,13 +113,23 @@ impl <S: Default + Debug> Program<S> {
                 EQUALS => self.equals(),
                 ADJUST_BASE_OFFSET => self.adj_base_offset(),
                 HALT  => break,
-                _ => return Err(format!("Unknown OpCode").into())
+                _ => return Err(format!("Unknown OpCode {}", code).into())
+            }
+            if let Some(breach) = self.bad_write.take() {
+                // Report the instruction that tried to write, not the next one
+                self.p = pointer;
+                return Err(breach.with(self.snapshot()).into());
             }
+            self.meter.tick();
         }
 
         Ok(&self.state)
     }
     
+    fn snapshot(&self) -> Snapshot<S> {
+        Snapshot { pointer: self.p, base_offset: self.base_offset, executed: self.meter.executed(), state: self.state.clone() }
+    }
+
     fn input(&mut self) {
         if let Some(input_supplier) = &self.input_fn {
             self.update_param(input_supplier(&self.state), 1);
@@ -126,11 +155,15 @@ impl <S: Default + Debug> Program<S> {
         };
         let index = self.p + param;
         let literal_value = *self.memory.get(&index).unwrap_or(&0);
-        match param_mode {
-            Position => self.memory.insert(literal_value as usize, value),
-            Relative => self.memory.insert((self.base_offset + literal_value) as usize, value),
+        let address = match param_mode {
+            Position => literal_value as usize,
+            Relative => (self.base_offset + literal_value) as usize,
             _ => panic!("Problem updating param1, unknown param mode")
         };
+        match self.meter.check_address(address) {
+            Ok(()) => { self.memory.insert(address, value); },
+            Err(breach) => self.bad_write = Some(breach)
+        }
     }
     
     fn adj_base_offset(&mut self) {
@@ -220,11 +253,26 @@ impl <S: Default + Debug> Program<S> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::opcode::ParameterMode::Relative;
+    use std::cell::RefCell;
+    use conformance::{Outcome, run_suite, ALL_FEATURES};
+
+    fn run(op_codes: &[i64], inputs: &[i64]) -> (Program<Vec<i64>>, Vec<i64>) {
+        let mut p : Program<Vec<i64>> = Program::new(&op_codes.to_vec());
+        let inputs: Vec<i64> = inputs.to_vec();
+        let inputs = RefCell::new(inputs.into_iter());
+        p.set_input_fn(move |_| inputs.borrow_mut().next().expect("Ran out of input"));
+        p.set_output_fn(|outputs, output| {
+            let mut outputs = outputs.clone();
+            outputs.push(output);
+            outputs
+        });
+        let outputs = p.exec().expect("Program failed").clone();
+        (p, outputs)
+    }
 
     #[test]
     fn decode() {
-        let (code, op1, op2, op3) = Program::decode(1002);
+        let (code, op1, op2, op3) = Program::<()>::decode(1002);
         assert_eq!(code, 2);
         assert_eq!(op1, Position);
         assert_eq!(op2, Immediate);
@@ -233,8 +281,8 @@ mod tests {
 
     #[test]
     fn program() {
-        let mut program = Program::new(&vec![1002, 4, 3, 4, 33]);
-        program.exec();
+        let (p, _) = run(&[1002, 4, 3, 4, 33], &[]);
+        assert_eq!(p.peek(4), 99);
     }
     
     #[test]
@@ -251,24 +299,53 @@ mod tests {
     #[test]
     fn test_base_pointer() {
         let input = vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
-        let mut p = Program::new(&input);
-        p.exec();
+        let (_, outputs) = run(&input, &[]);
+        assert_eq!(outputs, input);
     }
     
     #[test]
     fn sixteen_digit() {
         let input = vec![1102,34915192,34915192,7,4,7,99,0];
-        let mut p = Program::new(&input);
-        p.exec();
+        let (_, outputs) = run(&input, &[]);
+        assert_eq!(outputs, vec![1219070632396864]);
+        assert_eq!(outputs[0].to_string().len(), 16);
     }
     
     #[test]
     fn number() {
-        
         let input = vec![104,1125899906842624,99];
-        let mut p = Program::new(&input);
-        p.exec();
-        
+        let (_, outputs) = run(&input, &[]);
+        assert_eq!(outputs, vec![1125899906842624]);
+    }
+
+    #[test]
+    fn limits() {
+        let mut p : Program<Vec<i64>> = Program::new(&vec![104, 7, 1105, 1, 0]);
+        p.set_output_fn(|outputs, output| [outputs.as_slice(), &[output]].concat());
+        p.set_limits(Limits { max_instructions: Some(5), ..Limits::default() });
+        let error = p.exec().unwrap_err();
+        assert_eq!(error.to_string(), "Ran more than 5 instructions at 2 after 5 instructions, state [7, 7, 7]");
+
+        let mut p : Program<()> = Program::new(&vec![109, -5, 21101, 1, 1, 205, 99]);
+        p.set_limits(Limits { max_address: Some(100), ..Limits::default() });
+        let error = p.exec().unwrap_err();
+        assert_eq!(error.to_string(), "Address 200 is past the limit of 100 at 2 after 1 instructions, state ()");
+
+        let mut p : Program<()> = Program::new(&vec![1101, 1, 1, 5, 99, 0]);
+        p.set_limits(Limits { max_instructions: Some(1), max_address: Some(5), ..Limits::default() });
+        p.exec().unwrap();
+        assert_eq!(p.executed(), 1);
+    }
+
+    #[test]
+    fn conformance() {
+        let report = run_suite("Program<S>", |case| {
+            let (p, outputs) = run(case.program, case.inputs);
+            let memory = (0..case.program.len()).map(|address| p.peek(address)).collect();
+            Ok(Outcome { outputs: Some(outputs), last_output: None, memory: Some(memory) })
+        });
+        assert_eq!(report.failures(), Vec::<String>::new());
+        report.assert_supports(&ALL_FEATURES);
     }
 }
 
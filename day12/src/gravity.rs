@@ -0,0 +1,100 @@
+use std::fmt::{Debug, Display};
+use std::marker::PhantomData;
+use std::ops::{Add, AddAssign, Mul, Sub};
+
+pub type Vector<T> = (T, T, T);
+
+/// What positions and velocities are made of.
+pub trait Scalar: Copy + Default + PartialEq + PartialOrd + Debug + Display
+    + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + AddAssign {
+    fn one() -> Self;
+    fn abs(self) -> Self;
+}
+
+impl Scalar for i32 {
+    fn one() -> i32 {
+        1
+    }
+
+    fn abs(self) -> i32 {
+        i32::abs(self)
+    }
+}
+
+impl Scalar for f64 {
+    fn one() -> f64 {
+        1.0
+    }
+
+    fn abs(self) -> f64 {
+        f64::abs(self)
+    }
+}
+
+pub fn add<T: Scalar>(a: Vector<T>, b: Vector<T>) -> Vector<T> {
+    (a.0 + b.0, a.1 + b.1, a.2 + b.2)
+}
+
+pub fn scale<T: Scalar>(v: Vector<T>, by: T) -> Vector<T> {
+    (v.0 * by, v.1 * by, v.2 * by)
+}
+
+/// How one body changes the velocity of another over one step.
+pub trait ForceLaw {
+    type Scalar: Scalar;
+
+    /// The change in velocity of a body at `on` caused by a body at `from`.
+    fn pull(&self, on: Vector<Self::Scalar>, from: Vector<Self::Scalar>) -> Vector<Self::Scalar>;
+}
+
+/// The puzzle's rule: on each axis, one unit towards the other body.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Signum;
+
+impl ForceLaw for Signum {
+    type Scalar = i32;
+
+    fn pull(&self, on: Vector<i32>, from: Vector<i32>) -> Vector<i32> {
+        ((from.0 - on.0).signum(), (from.1 - on.1).signum(), (from.2 - on.2).signum())
+    }
+}
+
+/// Newtonian gravity with every body the same mass. Bodies in the same
+/// place don't pull on each other.
+#[derive(Debug, Clone, Copy)]
+pub struct InverseSquare {
+    pub strength: f64
+}
+
+impl ForceLaw for InverseSquare {
+    type Scalar = f64;
+
+    fn pull(&self, on: Vector<f64>, from: Vector<f64>) -> Vector<f64> {
+        let d = (from.0 - on.0, from.1 - on.1, from.2 - on.2);
+        let r2 = d.0 * d.0 + d.1 * d.1 + d.2 * d.2;
+        if r2 == 0.0 {
+            return (0.0, 0.0, 0.0);
+        }
+        scale(d, self.strength / (r2 * r2.sqrt()))
+    }
+}
+
+/// Any function of the two positions, in the same order as `pull`.
+pub struct Custom<T, F> {
+    pull: F,
+    scalar: PhantomData<T>
+}
+
+impl<T: Scalar, F: Fn(Vector<T>, Vector<T>) -> Vector<T>> Custom<T, F> {
+    pub fn new(pull: F) -> Custom<T, F> {
+        Custom { pull, scalar: PhantomData }
+    }
+}
+
+impl<T: Scalar, F: Fn(Vector<T>, Vector<T>) -> Vector<T>> ForceLaw for Custom<T, F> {
+    type Scalar = T;
+
+    fn pull(&self, on: Vector<T>, from: Vector<T>) -> Vector<T> {
+        (self.pull)(on, from)
+    }
+}
@@ -1,14 +1,10 @@
 use std::str::FromStr;
+use std::collections::HashMap;
 use itertools::Itertools;
 use std::fmt::{Display, Formatter, Error};
+use crate::gravity::{ForceLaw, Scalar, Signum, Vector, add, scale};
 
-type Position = (i32, i32, i32);
-type Velocity = (i32, i32, i32);
-
-const TEST_INPUT: &str = "<x=-1, y=0, z=2>
-<x=2, y=-10, z=-7>
-<x=4, y=-8, z=8>
-<x=3, y=5, z=-1>";
+pub mod gravity;
 
 const PUZZLE_INPUT: &str =  "<x=12, y=0, z=-15>
 <x=-8, y=-5, z=-10>
@@ -17,158 +13,277 @@ const PUZZLE_INPUT: &str =  "<x=12, y=0, z=-15>
 
 fn main() {
     let mut space : Space = PUZZLE_INPUT.parse().unwrap();
+    eprintln!("space.cycles().period() = {:#?}", space.cycles().map(|cycles| cycles.period()));
     space.tick_for(1000);
     eprintln!("space.total_energy() = {:#?}", space.total_energy());
 }
 
 #[derive(Debug, Clone, PartialEq)]
-struct Moon {
-    id: usize,
-    position: Position,
-    velocity: Velocity
+pub struct Moon<T: Scalar = i32> {
+    position: Vector<T>,
+    velocity: Vector<T>
 }
 
+/// Moons moving under a force law, `step` time units per tick.
 #[derive(Debug)]
-struct Space {
-    moons: Vec<Moon>
+pub struct Space<L: ForceLaw = Signum> {
+    moons: Vec<Moon<L::Scalar>>,
+    law: L,
+    step: L::Scalar
 }
 
-impl Moon {
-    pub fn apply_gravity(&mut self, other: &Moon) {
-        let x = Moon::calc_gravity(self.position.0, other.position.0);
-        let y = Moon::calc_gravity(self.position.1, other.position.1);
-        let z = Moon::calc_gravity(self.position.2, other.position.2);
-        self.velocity.0 += x;
-        self.velocity.1 += y;
-        self.velocity.2 += z;
+impl<T: Scalar> Moon<T> {
+    /// A moon at rest.
+    pub fn at(position: Vector<T>) -> Moon<T> {
+        Moon { position, velocity: Default::default() }
     }
 
-    pub fn apply_velocity(&mut self) {
-        self.position.0 += self.velocity.0;
-        self.position.1 += self.velocity.1;
-        self.position.2 += self.velocity.2;
+    pub fn position(&self) -> Vector<T> {
+        self.position
     }
 
-    pub fn  kinetic_energy(&self) -> i32 {
-        let k = self.velocity.0.abs() + self.velocity.1.abs() + self.velocity.2.abs();
-        eprintln!("k = {:#?}", k);
-        k
+    pub fn velocity(&self) -> Vector<T> {
+        self.velocity
     }
 
-    pub fn potential_energy(&self) -> i32 {
+    pub fn  kinetic_energy(&self) -> T {
+        self.velocity.0.abs() + self.velocity.1.abs() + self.velocity.2.abs()
+    }
+
+    pub fn potential_energy(&self) -> T {
         self.position.0.abs() + self.position.1.abs() + self.position.2.abs()
     }
     
-    pub fn energy(&self) -> i32 {
+    pub fn energy(&self) -> T {
         self.potential_energy() * self.kinetic_energy()
     }
+}
 
-    fn calc_gravity(a: i32, b: i32) -> i32 {
-        if a < b { 1 } else if a > b { -1 } else { 0 }
+
+impl<L: ForceLaw> Space<L> {
+    pub fn new(moons: Vec<Moon<L::Scalar>>, law: L) -> Space<L> {
+        Space { moons, law, step: L::Scalar::one() }
+    }
+
+    /// How much time passes each tick. Velocity changes by the pull times
+    /// the step, then position by the new velocity times the step.
+    pub fn set_step(&mut self, step: L::Scalar) {
+        self.step = step;
     }
-}
 
+    pub fn moons(&self) -> &[Moon<L::Scalar>] {
+        &self.moons
+    }
 
-impl Space {
     pub fn tick_for(&mut self, n: usize) {
-//        eprintln!("After 0" );
-//        self.moons.iter().for_each(|moon| eprintln!("{}", moon));
-        (1..=n).for_each(|step| {
-        
-            self.tick();
-//            eprintln!("After {}", step );
-//            self.moons.iter().for_each(|moon| eprintln!("{}", moon));
-        })
+        (0..n).for_each(|_| self.tick())
     }
 
+    /// Works out every pull from where the moons are now, then moves them
+    /// all, so no moon sees another's new position part way through.
     fn tick(&mut self) {
-        let initial : Vec<Moon> = self.moons.clone();
-        let other_moons = self.moons.clone();
-        (0..self.moons.len()).combinations(2).for_each(| mut moon_tuple|{
+        let mut pulls = vec![Vector::<L::Scalar>::default(); self.moons.len()];
+        for (i, j) in (0..self.moons.len()).tuple_combinations() {
+            let (a, b) = (self.moons[i].position, self.moons[j].position);
+            pulls[i] = add(pulls[i], self.law.pull(a, b));
+            pulls[j] = add(pulls[j], self.law.pull(b, a));
+        }
 
-            let moon1 = &mut self.moons[moon_tuple[0]];
-            let moon2 = other_moons.iter().find(|moon| moon.id == moon_tuple[1]).unwrap();
-                
-            moon1.apply_gravity(&moon2);
+        let step = self.step;
+        for (moon, pull) in self.moons.iter_mut().zip(pulls) {
+            moon.velocity = add(moon.velocity, scale(pull, step));
+            moon.position = add(moon.position, scale(moon.velocity, step));
+        }
+    }
+    
+    pub fn total_energy(&self) -> L::Scalar {
+        self.moons.iter().fold(L::Scalar::default(), |total, moon| total + moon.energy())
+    }
+}
 
-            let moon1 = other_moons.iter().find(|moon| moon.id == moon_tuple[0]).unwrap();
-            let moon2 = &mut self.moons[moon_tuple[1]];
+/// Every moon's position and velocity on one axis.
+type AxisState = Vec<(i32, i32)>;
 
-            moon2.apply_gravity(&moon1);
-        });
-        
-        
+/// Under the puzzle's law each axis moves on its own, and each one
+/// eventually repeats. `states` runs from the start until just before the
+/// first repeat, which goes back to `states[start]`.
+struct AxisCycle {
+    start: usize,
+    states: Vec<AxisState>
+}
 
-        &self.moons.iter_mut().for_each(|moon| moon.apply_velocity());
+impl AxisCycle {
+    fn period(&self) -> u64 {
+        (self.states.len() - self.start) as u64
+    }
 
-        
+    fn state_at(&self, step: u64) -> &AxisState {
+        let start = self.start as u64;
+        let index = if step < start { step } else { start + (step - start) % self.period() };
+        &self.states[index as usize]
     }
-    
-    fn print_positions(&self) {
-        self.moons.iter().for_each(|m1|{
-            eprintln!("{},{},{}",
-                      m1.position.0,
-                      m1.position.1,
-                      m1.position.2 
-            );
-        });
+}
+
+/// The cycles each axis of a `Space` falls into, from which any step can
+/// be worked out without simulating up to it.
+pub struct Cycles {
+    axes: Vec<AxisCycle>,
+    step: i32
+}
+
+impl Cycles {
+    /// Ticks until the whole system is back in a state it has been in.
+    pub fn period(&self) -> u64 {
+        self.axes.iter().map(AxisCycle::period).fold(1, lcm)
     }
-    
-    fn print_change_of_position(&self) {
-        self.moons.iter().zip(initial.iter()).take(1).for_each(|(m1, m2)|{
-            eprintln!("{},{},{}",
-                      m1.position.0 - m2.position.0,
-                      m1.position.1 - m2.position.1,
-                      m1.position.2 - m2.position.2
-            );
-        });
+
+    /// Ticks before the system is in its cycle, which is 0 when it comes
+    /// back to where it started.
+    pub fn start(&self) -> u64 {
+        self.axes.iter().map(|axis| axis.start as u64).max().unwrap_or(0)
     }
-    
-    
-    pub fn total_energy(&self) -> i32 {
-        self.moons.iter().map(|moon| moon.energy()).sum()
+
+    pub fn state_at(&self, step: u64) -> Space {
+        let axes: Vec<&AxisState> = self.axes.iter().map(|axis| axis.state_at(step)).collect();
+        let moons = (0..axes[0].len())
+            .map(|moon| Moon {
+                position: (axes[0][moon].0, axes[1][moon].0, axes[2][moon].0),
+                velocity: (axes[0][moon].1, axes[1][moon].1, axes[2][moon].1)
+            })
+            .collect();
+        let mut space = Space::new(moons, Signum);
+        space.set_step(self.step);
+        space
+    }
+
+    pub fn energy_at(&self, step: u64) -> i32 {
+        self.state_at(step).total_energy()
+    }
+}
+
+/// How long `Space::cycles` simulates before giving up. The puzzle's
+/// axes all repeat within a few hundred thousand ticks.
+const MAX_CYCLE_TICKS: usize = 1_000_000;
+
+impl Space {
+    /// Simulates until every axis has repeated, hashing each axis state
+    /// to spot the repeat. Nothing guarantees a repeat, particularly with a
+    /// step other than 1 or moons that start off moving, so this is None if
+    /// some axis hasn't repeated within `MAX_CYCLE_TICKS`.
+    pub fn cycles(&self) -> Option<Cycles> {
+        self.cycles_within(MAX_CYCLE_TICKS)
+    }
+
+    /// `cycles`, giving up after `max_ticks`.
+    pub fn cycles_within(&self, max_ticks: usize) -> Option<Cycles> {
+        let mut space = Space::new(self.moons.clone(), Signum);
+        space.set_step(self.step);
+        let mut seen: Vec<HashMap<AxisState, usize>> = vec![HashMap::new(); 3];
+        let mut states: Vec<Vec<AxisState>> = vec![vec![]; 3];
+        let mut starts: Vec<Option<usize>> = vec![None; 3];
+
+        for tick in 0..=max_ticks {
+            for axis in 0..3 {
+                if starts[axis].is_some() {
+                    continue;
+                }
+                let state = space.axis(axis);
+                match seen[axis].get(&state) {
+                    Some(&start) => starts[axis] = Some(start),
+                    None => {
+                        seen[axis].insert(state.clone(), tick);
+                        states[axis].push(state);
+                    }
+                }
+            }
+            if starts.iter().all(Option::is_some) {
+                let axes = starts.into_iter()
+                    .zip(states)
+                    .map(|(start, states)| AxisCycle { start: start.unwrap(), states })
+                    .collect();
+                return Some(Cycles { axes, step: self.step });
+            }
+            space.tick();
+        }
+        None
+    }
+
+    /// The system after `step` ticks, found from `cycles()` where there
+    /// are some and by simulating otherwise. For more than one query it's
+    /// quicker to keep hold of `cycles()`.
+    pub fn state_at(&self, step: u64) -> Space {
+        match self.cycles() {
+            Some(cycles) => cycles.state_at(step),
+            None => {
+                let mut space = Space::new(self.moons.clone(), Signum);
+                space.set_step(self.step);
+                for _ in 0..step {
+                    space.tick();
+                }
+                space
+            }
+        }
+    }
+
+    pub fn energy_at(&self, step: u64) -> i32 {
+        self.state_at(step).total_energy()
+    }
+
+    fn axis(&self, axis: usize) -> AxisState {
+        let component = |v: Vector<i32>| match axis {
+            0 => v.0,
+            1 => v.1,
+            _ => v.2
+        };
+        self.moons.iter().map(|moon| (component(moon.position), component(moon.velocity))).collect()
     }
 }
 
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    a / gcd(a, b) * b
+}
+
 impl FromStr for Space {
     type Err = ();
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let moons = PUZZLE_INPUT
+        let moons = input
             .lines()
-            .enumerate()
-            .map(|(i, s)| parse(i, s))
+            .map(parse)
             .collect::<Vec<Moon>>();
-        Ok(Space{ moons })
+        Ok(Space::new(moons, Signum))
     }
 }
 
 
-fn parse(id: usize, line: &str) -> Moon {
-    let mut line = line;
+fn parse(line: &str) -> Moon {
     let line = line.replace("<","")
         .replace(">","");
-    let n = line.split(",")
+    let n = line.split(',')
         .map(|s| s.trim())
         .map(|s| {
             let s = &s[2..].to_string();
             s.parse::<i32>().unwrap()
         })
         .collect::<Vec<i32>>();
-    Moon { id, position: (n[0], n[1], n[2]), velocity: (0, 0, 0) }
+    Moon::at((n[0], n[1], n[2]))
 }
 
-impl Display for Space {
+impl<L: ForceLaw> Display for Space<L> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         for moon in &self.moons {
-            writeln!(f, "{}", moon);
+            writeln!(f, "{}", moon)?;
         }
         
         Ok(())
     }
 }
 
-impl Display for Moon {
+impl<T: Scalar> Display for Moon<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         write!(f, "pos=<x={:2}, y={:2}, z={:2}>, vel=<x={:2}, y={:2}, z={:2}>", 
                  self.position.0,
@@ -185,6 +300,12 @@ impl Display for Moon {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::gravity::{Custom, InverseSquare};
+
+    const TEST_INPUT: &str = "<x=-1, y=0, z=2>
+<x=2, y=-10, z=-7>
+<x=4, y=-8, z=8>
+<x=3, y=5, z=-1>";
 
     #[test]
     fn test() {
@@ -216,4 +337,93 @@ mod tests {
         let mut space : Space = TEST_INPUT.parse().unwrap();
         space.tick_for(2772);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn custom_law() {
+        let input2 = "<x=-8, y=-10, z=0>
+<x=5, y=5, z=10>
+<x=2, y=-7, z=3>
+<x=9, y=-8, z=-3>";
+        let moons: Vec<Moon> = input2.lines().map(parse).collect();
+        let signum = |a: i32, b: i32| if a < b { 1 } else if a > b { -1 } else { 0 };
+        let law = Custom::new(|on: Vector<i32>, from: Vector<i32>| {
+            (signum(on.0, from.0), signum(on.1, from.1), signum(on.2, from.2))
+        });
+        let mut space = Space::new(moons, law);
+        space.tick_for(100);
+        assert_eq!(space.total_energy(), 1940);
+
+        // Pulling away instead sends everything off to infinity
+        let moons: Vec<Moon> = input2.lines().map(parse).collect();
+        let mut space = Space::new(moons, Custom::new(|on, from| scale(Signum.pull(on, from), -1)));
+        space.tick_for(100);
+        assert!(space.total_energy() > 1_000_000);
+    }
+
+    #[test]
+    fn inverse_square() {
+        let moons = vec![Moon::at((-1.0, 0.0, 0.0)), Moon::at((1.0, 0.0, 0.0))];
+        let mut space = Space::new(moons, InverseSquare { strength: 4.0 });
+        space.set_step(0.5);
+        space.tick();
+        assert_eq!(space.moons()[0].velocity(), (0.5, 0.0, 0.0));
+        assert_eq!(space.moons()[0].position(), (-0.75, 0.0, 0.0));
+        assert_eq!(space.moons()[1].position(), (0.75, 0.0, 0.0));
+
+        // Equal masses, so the total momentum stays at nothing
+        let moons = vec![Moon::at((0.0, 0.0, 0.0)), Moon::at((3.0, 1.0, 0.0)), Moon::at((-2.0, 4.0, 1.0))];
+        let mut space = Space::new(moons, InverseSquare { strength: 1.0 });
+        space.set_step(0.01);
+        space.tick_for(500);
+        let momentum = space.moons().iter().fold((0.0, 0.0, 0.0), |total, moon| add(total, moon.velocity()));
+        assert!(momentum.0.abs() < 1e-9 && momentum.1.abs() < 1e-9 && momentum.2.abs() < 1e-9);
+    }
+
+    #[test]
+    fn periods() {
+        let space : Space = TEST_INPUT.parse().unwrap();
+        let cycles = space.cycles().unwrap();
+        assert_eq!((cycles.start(), cycles.period()), (0, 2772));
+
+        let space : Space = "<x=-8, y=-10, z=0>
+<x=5, y=5, z=10>
+<x=2, y=-7, z=3>
+<x=9, y=-8, z=-3>".parse().unwrap();
+        assert_eq!(space.cycles().unwrap().period(), 4686774924);
+    }
+
+    #[test]
+    fn no_cycle() {
+        // A lone moon drifting off never comes back
+        let drifting = Moon { position: (0, 0, 0), velocity: (1, 0, 0) };
+        let space = Space::new(vec![drifting], Signum);
+        assert!(space.cycles_within(1000).is_none());
+    }
+
+    #[test]
+    fn state_at_matches_simulation() {
+        let space : Space = TEST_INPUT.parse().unwrap();
+        let cycles = space.cycles().unwrap();
+        let mut simulated : Space = TEST_INPUT.parse().unwrap();
+        for step in 0..3000 {
+            assert_eq!(cycles.state_at(step).moons(), simulated.moons(), "step {}", step);
+            simulated.tick();
+        }
+        assert_eq!(space.energy_at(10), 179);
+    }
+
+    #[test]
+    fn fast_forward() {
+        let space : Space = PUZZLE_INPUT.parse().unwrap();
+        let cycles = space.cycles().unwrap();
+        assert_eq!(cycles.energy_at(1000), 7636);
+
+        // Far past the end of the first cycle, but only a little way into
+        // a later one
+        let far = cycles.period() * 1000 + 1234;
+        let mut simulated : Space = PUZZLE_INPUT.parse().unwrap();
+        simulated.tick_for(1234);
+        assert_eq!(cycles.state_at(far).moons(), simulated.moons());
+        assert_eq!(cycles.energy_at(far), simulated.total_energy());
+    }
+}
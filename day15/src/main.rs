@@ -1,14 +1,18 @@
-#![feature(entry_insert)]
 use std::fs;
-use crate::opcode::Program;
-use std::collections::HashMap;
+use std::env;
+use std::process;
+use intcode::{Program, Limits, LimitExceeded};
+use intcode::ascii;
+use intcode::cfg::Cfg;
+use std::time::Duration;
 use crate::Direction::{North, South, West, East};
 use itertools::Itertools;
 
 
 type Coord = (i32, i32);
 
-pub mod opcode;
+const MAX_INSTRUCTIONS : u64 = 10_000_000;
+const TIMEOUT : Duration = Duration::from_secs(30);
 
 #[derive(Debug,Clone)]
 enum Direction {
@@ -18,11 +22,15 @@ enum Direction {
     West
 }
 
-#[derive(Debug,Clone)]
-enum Status {
-    Wall, 
-    SuccessfulMove,
-    FoundOxygen
+impl From<Direction> for i64 {
+    fn from(d: Direction) -> Self {
+        match d {
+            North => 1,
+            South => 2,
+            West => 3,
+            East => 4
+        }
+    }
 }
 
 impl From<i32> for Direction {
@@ -37,42 +45,76 @@ impl From<i32> for Direction {
     }
 }
 
+/// Explores the maze in input.txt. Otherwise `--ascii <file>` runs an ASCII
+/// program interactively against the terminal, and `--disassemble <file>`
+/// or `--cfg <file>` print a program's code as a listing or Graphviz DOT.
 fn main() {
-    let op_codes: Vec<i64> = fs::read_to_string("input.txt")
-        .expect("Something went wrong reading the file")
-        .split(",")
-        .map(|x| x.parse::<i64>().unwrap())
-        .collect();
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["--ascii", file] => {
+            match ascii::interactive(&read_program(file)) {
+                Ok(output) if !output.values.is_empty() => eprintln!("values = {:?}", output.values),
+                Ok(_) => {},
+                Err(e) => {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                }
+            }
+            return;
+        },
+        ["--disassemble", file] => {
+            print!("{}", Cfg::from_memory(&read_program(file)));
+            return;
+        },
+        ["--cfg", file] => {
+            print!("{}", Cfg::from_memory(&read_program(file)).to_dot());
+            return;
+        },
+        _ => {}
+    }
+
+    let op_codes = read_program("input.txt");
 
     let mut p : Program<State> = Program::new(&op_codes);
 
     p.set_input_fn(|state| {
-        *state.next_move().into()
+        state.next_move().into()
     });
 
-    p.set_output_fn(|state,output| {
-        let mut state = state.clone();
-        let position = state.position;
-        
-        
-        
-        state
+    p.set_output_fn(|state, _output| {
+        state.clone()
     });
 
-    let final_state = p.exec().expect("bad");
+    // The droid doesn't know how to search yet, so it walks North forever
+    p.set_limits(Limits { max_instructions: Some(MAX_INSTRUCTIONS), timeout: Some(TIMEOUT), ..Limits::default() });
+    let final_state = match p.exec() {
+        Ok(final_state) => final_state.clone(),
+        Err(e) => match e.downcast_ref::<LimitExceeded<State>>() {
+            Some(limit) => {
+                eprintln!("Gave up after {} instructions", limit.snapshot().executed);
+                limit.snapshot().state.clone()
+            },
+            None => panic!("Droid program failed: {}", e)
+        }
+    };
 
     eprintln!("unique locations painted = {}", final_state.seen.iter().unique().count());
 
     final_state.write();
+}
 
+fn read_program(file: &str) -> Vec<i64> {
+    fs::read_to_string(file)
+        .expect("Something went wrong reading the file")
+        .trim()
+        .split(',')
+        .map(|x| x.parse::<i64>().unwrap())
+        .collect()
 }
 
-#[derive(Debug,Clone)]
+#[derive(Debug,Clone,Default)]
 struct State {
-    canvas: HashMap<Coord, i64>,
     position: Coord,
-    count: usize,
-    facing: Direction,
     seen: Vec<Coord>
 }
 
@@ -81,16 +123,21 @@ impl State {
         North
     } 
     
-    
-}
-
-impl Default for State {
-    fn default() -> Self {
-        let mut canvas: HashMap<Coord, i64> = HashMap::new();
-        canvas.insert((0,0), 1);
-
-        State {
-           canvas, position: (0,0), count: 0 , facing: North, seen: vec![]
+    /// Draws where the droid has been, with a D where it is now.
+    pub fn write(&self) {
+        let explored = || self.seen.iter().chain(std::iter::once(&self.position));
+        let (min_x, max_x) = explored().map(|&(x, _)| x).minmax().into_option().unwrap();
+        let (min_y, max_y) = explored().map(|&(_, y)| y).minmax().into_option().unwrap();
+
+        for y in min_y..=max_y {
+            let row: String = (min_x..=max_x)
+                .map(|x| match (x, y) {
+                    position if position == self.position => 'D',
+                    position if self.seen.contains(&position) => '.',
+                    _ => ' '
+                })
+                .collect();
+            println!("{}", row);
         }
     }
 }
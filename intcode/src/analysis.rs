@@ -0,0 +1,179 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use crate::{Program, Step, instruction_width};
+
+/// Where a write landed relative to the program as it was loaded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    Image,
+    Heap
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Write {
+    pub address: usize,
+    pub value: i64,
+    pub region: Region,
+    /// The address had already been executed as part of an instruction.
+    pub over_code: bool
+}
+
+/// One executed instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub step: usize,
+    pub pointer: usize,
+    pub instruction: Vec<i64>,
+    pub write: Option<Write>,
+    /// Cells of this instruction that were rewritten before it ran, with
+    /// the step that wrote them.
+    pub modified: Vec<(usize, usize)>
+}
+
+pub struct Analysis {
+    pub lines: Vec<Line>
+}
+
+impl Analysis {
+    /// Steps that wrote over code that had already run.
+    pub fn self_modifying_writes(&self) -> Vec<&Line> {
+        self.lines.iter().filter(|l| l.write.as_ref().is_some_and(|w| w.over_code)).collect()
+    }
+
+    /// Steps that ran an instruction the program had rewritten.
+    pub fn modified_executions(&self) -> Vec<&Line> {
+        self.lines.iter().filter(|l| !l.modified.is_empty()).collect()
+    }
+
+    pub fn writes_to(&self, region: Region) -> Vec<&Write> {
+        self.lines.iter().filter_map(|l| l.write.as_ref()).filter(|w| w.region == region).collect()
+    }
+}
+
+impl Display for Line {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let instruction = self.instruction.iter().map(i64::to_string).collect::<Vec<_>>().join(",");
+        write!(f, "{:>6} {:>5}  {:<28}", self.step, self.pointer, instruction)?;
+        if let Some(w) = &self.write {
+            let region = match w.region { Region::Image => "image", Region::Heap => "heap" };
+            write!(f, " [{}]={} {}", w.address, w.value, region)?;
+            if w.over_code {
+                write!(f, " OVERWRITES CODE")?;
+            }
+        }
+        for (address, step) in &self.modified {
+            write!(f, " MODIFIED [{}] at step {}", address, step)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Analysis {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line.to_string().trim_end())?;
+        }
+        writeln!(f, "{} steps, {} writes to the image, {} to the heap, {} over code, {} modified instructions run",
+                 self.lines.len(),
+                 self.writes_to(Region::Image).len(),
+                 self.writes_to(Region::Heap).len(),
+                 self.self_modifying_writes().len(),
+                 self.modified_executions().len())
+    }
+}
+
+/// Runs a program that is ready to go, recording every instruction it
+/// executes and every write it makes, for at most `max_steps` steps.
+pub fn analyse<S: Default + Debug + Clone + 'static>(program: &mut Program<S>, max_steps: usize) -> Result<Analysis, Box<dyn Error>> {
+    let mut lines = vec![];
+    let mut executed: HashSet<usize> = HashSet::new();
+    let mut written_at: HashMap<usize, usize> = HashMap::new();
+
+    for step in 0..max_steps {
+        let pointer = program.pointer();
+        let width = instruction_width(program.peek(pointer)).unwrap_or(1);
+        let span = pointer..pointer + width;
+        let instruction = span.clone().map(|address| program.peek(address)).collect();
+        let modified = span.clone()
+            .filter_map(|address| written_at.get(&address).map(|&at| (address, at)))
+            .collect();
+        executed.extend(span);
+
+        let halted = program.step()? == Step::Halted;
+        let write = program.last_write().map(|(address, value)| {
+            written_at.insert(address, step);
+            Write {
+                address,
+                value,
+                region: if address < program.image_len() { Region::Image } else { Region::Heap },
+                over_code: executed.contains(&address)
+            }
+        });
+        lines.push(Line { step, pointer, instruction, write, modified });
+
+        if halted {
+            return Ok(Analysis { lines });
+        }
+    }
+
+    Err(format!("Program didn't halt within {} steps", max_steps).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patches_its_own_halt() {
+        // Turns the 33 at address 4 into a HALT, then runs it. That isn't a
+        // write over code, as address 4 hadn't run yet.
+        let mut p : Program<()> = Program::new(&vec![1002, 4, 3, 4, 33]);
+        let analysis = analyse(&mut p, 10).unwrap();
+
+        assert_eq!(analysis.lines.len(), 2);
+        let patch = &analysis.lines[0];
+        assert_eq!(patch.write, Some(Write { address: 4, value: 99, region: Region::Image, over_code: false }));
+        assert_eq!(analysis.lines[1].modified, vec![(4, 0)]);
+        assert!(analysis.self_modifying_writes().is_empty());
+        assert_eq!(analysis.modified_executions()[0].pointer, 4);
+    }
+
+    #[test]
+    fn separates_image_and_heap() {
+        // Stores to 100 on the heap, then to 9 in the image which never runs
+        let mut p : Program<()> = Program::new(&vec![1101, 1, 2, 100, 1101, 3, 4, 9, 99, 0]);
+        let analysis = analyse(&mut p, 10).unwrap();
+
+        assert_eq!(analysis.writes_to(Region::Heap)[0].address, 100);
+        assert_eq!(analysis.writes_to(Region::Image)[0].address, 9);
+        assert!(analysis.self_modifying_writes().is_empty());
+        assert!(analysis.modified_executions().is_empty());
+        assert_eq!(analysis.lines[1].to_string(), "     1     4  1101,3,4,9                   [9]=7 image");
+    }
+
+    #[test]
+    fn rewritten_jump_target() {
+        // The first instruction's operand is patched, then jumped back to
+        let program = vec![
+            1101, 0, 0, 20,    // 0: mem[20] = 0 + 0, operand 1 becomes 5 on the second pass
+            1007, 20, 1, 21,   // 4: mem[21] = mem[20] < 1
+            1101, 5, 0, 1,     // 8: mem[1] = 5
+            1005, 21, 0,       // 12: back to 0 if mem[21]
+            99];
+        let mut p : Program<()> = Program::new(&program);
+        let analysis = analyse(&mut p, 100).unwrap();
+
+        assert_eq!(p.peek(20), 5);
+        let rerun = analysis.modified_executions();
+        assert_eq!(rerun.len(), 1);
+        assert_eq!((rerun[0].pointer, rerun[0].modified.clone()), (0, vec![(1, 2)]));
+        assert!(analysis.to_string().ends_with("2 over code, 1 modified instructions run\n"));
+    }
+
+    #[test]
+    fn gives_up() {
+        let mut p : Program<()> = Program::new(&vec![1105, 1, 0]);
+        assert!(analyse(&mut p, 50).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+use crate::{Program, Step};
+
+/// Largest value that is treated as a character rather than a number.
+const MAX_ASCII : i64 = 127;
+
+/// Everything an ASCII program printed. Characters are collected into
+/// `text`, anything outside the ASCII range is kept as a number in `values`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AsciiOutput {
+    pub text: String,
+    pub values: Vec<i64>
+}
+
+impl AsciiOutput {
+    /// Adds one output, returning the character if it was printable.
+    fn push(&mut self, output: i64) -> Option<char> {
+        if (0..=MAX_ASCII).contains(&output) {
+            let c = output as u8 as char;
+            self.text.push(c);
+            Some(c)
+        } else {
+            self.values.push(output);
+            None
+        }
+    }
+
+    pub fn lines(&self) -> Vec<&str> {
+        self.text.lines().collect()
+    }
+}
+
+/// Where `attach` records why it couldn't give the program the input it
+/// asked for.
+pub type InputFailure = Rc<RefCell<Option<String>>>;
+
+/// A line of text as the character codes a program reads, newline included.
+pub fn encode(line: &str) -> Result<Vec<i64>, String> {
+    line.chars()
+        .chain(Some('\n'))
+        .map(|c| if c.is_ascii() { Ok(c as i64) } else { Err(format!("'{}' is not ASCII", c)) })
+        .collect()
+}
+
+/// Wires a program up to read lines from `next_line` whenever it runs out
+/// of input. With `echo` set, output goes to stdout as it arrives. Running
+/// out of lines or reading one that isn't ASCII feeds the program a 0 and
+/// records the reason in the returned slot, which `exec` turns into an error.
+pub fn attach<F>(program: &mut Program<AsciiOutput>, next_line: F, echo: bool) -> InputFailure
+    where F: FnMut() -> Option<String> + 'static {
+    let next_line = RefCell::new(next_line);
+    let pending = RefCell::new(VecDeque::new());
+    let failure: InputFailure = Rc::new(RefCell::new(None));
+
+    let failed = Rc::clone(&failure);
+    program.set_input_fn(move |_| {
+        let mut pending = pending.borrow_mut();
+        while pending.is_empty() {
+            let encoded = (next_line.borrow_mut())()
+                .ok_or_else(|| "Program asked for more input than it was given".to_string())
+                .and_then(|line| encode(&line));
+            match encoded {
+                Ok(codes) => pending.extend(codes),
+                Err(e) => {
+                    *failed.borrow_mut() = Some(e);
+                    return 0;
+                }
+            }
+        }
+        pending.pop_front().unwrap()
+    });
+
+    program.set_output_fn(move |output, value| {
+        let mut output = output.clone();
+        let printed = output.push(value);
+        if echo {
+            match printed {
+                Some(c) => print!("{}", c),
+                None => println!("{}", value)
+            }
+            io::stdout().flush().expect("Couldn't write to stdout");
+        }
+        output
+    });
+    failure
+}
+
+/// Runs a program wired up by `attach` until it halts, or until it asks for
+/// input that couldn't be given.
+pub fn exec(program: &mut Program<AsciiOutput>, failure: &InputFailure) -> Result<AsciiOutput, Box<dyn Error>> {
+    loop {
+        match program.step()? {
+            Step::Halted => return Ok(program.state().clone()),
+            Step::Input(_) => if let Some(e) = failure.borrow_mut().take() {
+                return Err(e.into());
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Runs a program with `lines` as its input and collects what it prints.
+pub fn run(op_codes: &[i64], lines: &[&str]) -> Result<AsciiOutput, Box<dyn Error>> {
+    let mut program = Program::new(&op_codes.to_vec());
+    let mut lines: VecDeque<String> = lines.iter().map(|l| l.to_string()).collect();
+    let failure = attach(&mut program, move || lines.pop_front(), false);
+    exec(&mut program, &failure)
+}
+
+/// Runs a program against the terminal, reading input from stdin a line at
+/// a time and printing output as it goes.
+pub fn interactive(op_codes: &[i64]) -> Result<AsciiOutput, Box<dyn Error>> {
+    let mut program = Program::new(&op_codes.to_vec());
+    let mut stdin = io::BufReader::new(io::stdin()).lines();
+    let failure = attach(&mut program, move || stdin.next().and_then(Result::ok), true);
+    exec(&mut program, &failure)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads a line and prints it back, then prints 1000.
+    const ECHO_LINE : [i64; 16] = [
+        3, 100,             // read a char into 100
+        4, 100,             // print it
+        1008, 100, 10, 101, // 101 = char == '\n'
+        1006, 101, 0,       // loop until the newline
+        104, 1000,          // print 1000
+        99, 0, 0];
+
+    #[test]
+    fn encodes_lines() {
+        assert_eq!(encode("Hi"), Ok(vec![72, 105, 10]));
+        assert!(encode("π").is_err());
+    }
+
+    #[test]
+    fn separates_text_and_values() {
+        let mut output = AsciiOutput::default();
+        for &value in &[72, 105, 10, 128, -1, 33] {
+            output.push(value);
+        }
+        assert_eq!(output.text, "Hi\n!");
+        assert_eq!(output.values, vec![128, -1]);
+        assert_eq!(output.lines(), vec!["Hi", "!"]);
+    }
+
+    #[test]
+    fn runs_a_program() {
+        let output = run(&ECHO_LINE, &["NOT A J"]).unwrap();
+        assert_eq!(output.text, "NOT A J\n");
+        assert_eq!(output.values, vec![1000]);
+    }
+
+    #[test]
+    fn bad_input_is_an_error() {
+        let error = run(&ECHO_LINE, &[]).unwrap_err();
+        assert_eq!(error.to_string(), "Program asked for more input than it was given");
+        let error = run(&ECHO_LINE, &["π"]).unwrap_err();
+        assert_eq!(error.to_string(), "'π' is not ASCII");
+    }
+}
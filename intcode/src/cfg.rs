@@ -0,0 +1,488 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use crate::{Program, ParameterMode, Step, instruction_width};
+use crate::{HALT, ADD, MUL, INPUT, OUTPUT, JUMP_TRUE, JUMP_FALSE, LESS_THAN, EQUALS, ADJUST_BASE_OFFSET};
+
+/// What an instruction does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    Add,
+    Mul,
+    Input,
+    Output,
+    JumpTrue,
+    JumpFalse,
+    LessThan,
+    Equals,
+    AdjustBase,
+    Halt,
+    /// A word that doesn't decode as an instruction, so is most likely data.
+    Unknown
+}
+
+impl From<i64> for Operation {
+    fn from(op_code: i64) -> Self {
+        match op_code % 100 {
+            ADD => Operation::Add,
+            MUL => Operation::Mul,
+            INPUT => Operation::Input,
+            OUTPUT => Operation::Output,
+            JUMP_TRUE => Operation::JumpTrue,
+            JUMP_FALSE => Operation::JumpFalse,
+            LESS_THAN => Operation::LessThan,
+            EQUALS => Operation::Equals,
+            ADJUST_BASE_OFFSET => Operation::AdjustBase,
+            HALT => Operation::Halt,
+            _ => Operation::Unknown
+        }
+    }
+}
+
+/// One decoded instruction, with the raw value of each parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub address: usize,
+    pub op_code: i64,
+    pub operation: Operation,
+    pub params: Vec<(ParameterMode, i64)>
+}
+
+impl Instruction {
+    /// The instruction at `address`, or an `Unknown` one taking up a single
+    /// word if it has a bad opcode or parameter mode or runs off the end of
+    /// memory. None past the end of memory.
+    pub fn decode(memory: &[i64], address: usize) -> Option<Instruction> {
+        let op_code = *memory.get(address)?;
+        let params = instruction_width(op_code)
+            .filter(|_| op_code > 0)
+            .and_then(|width| (1..width)
+                .map(|i| {
+                    let mode = (op_code / 10_i64.pow(i as u32 + 1)) % 10;
+                    let value = *memory.get(address + i)?;
+                    if mode > 2 { None } else { Some(((mode as u32).into(), value)) }
+                })
+                .collect::<Option<Vec<_>>>());
+        Some(match params {
+            Some(params) => Instruction { address, op_code, operation: op_code.into(), params },
+            None => Instruction { address, op_code, operation: Operation::Unknown, params: vec![] }
+        })
+    }
+
+    pub fn next(&self) -> usize {
+        self.address + self.params.len() + 1
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self.operation {
+            Operation::Add => "ADD",
+            Operation::Mul => "MUL",
+            Operation::Input => "IN",
+            Operation::Output => "OUT",
+            Operation::JumpTrue => "JT",
+            Operation::JumpFalse => "JF",
+            Operation::LessThan => "LT",
+            Operation::Equals => "EQ",
+            Operation::AdjustBase => "ARB",
+            Operation::Halt => "HALT",
+            Operation::Unknown => "DATA"
+        }
+    }
+
+    fn is_jump(&self) -> bool {
+        matches!(self.operation, Operation::JumpTrue | Operation::JumpFalse)
+    }
+
+    /// Execution can't carry on to the next word: the program stops, or
+    /// this isn't code at all.
+    fn ends_flow(&self) -> bool {
+        matches!(self.operation, Operation::Halt | Operation::Unknown)
+    }
+
+    /// Where a jump goes, if it doesn't depend on memory.
+    pub fn static_target(&self) -> Option<usize> {
+        match self.params.get(1) {
+            Some((ParameterMode::Immediate, target)) if self.is_jump() && *target >= 0 => Some(*target as usize),
+            _ => None
+        }
+    }
+
+    /// A jump whose condition is a constant, so it always or never jumps.
+    fn constant_condition(&self) -> Option<bool> {
+        match self.params.first() {
+            Some((ParameterMode::Immediate, value)) if self.is_jump() => Some((*value != 0) == (self.operation == Operation::JumpTrue)),
+            _ => None
+        }
+    }
+
+    /// Jumps to an address stored relative to the base, which is how
+    /// compiled Intcode returns from a subroutine.
+    pub fn is_return(&self) -> bool {
+        self.is_jump() && self.constant_condition() == Some(true)
+            && matches!(self.params.get(1), Some((ParameterMode::Relative, _)))
+    }
+
+    /// Stores a constant relative to the base, as a caller does with its
+    /// return address.
+    fn pushed_constant(&self) -> Option<i64> {
+        match self.params.as_slice() {
+            [(ParameterMode::Immediate, a), (ParameterMode::Immediate, b), (ParameterMode::Relative, _)] => {
+                match self.operation {
+                    Operation::Add => a.checked_add(*b),
+                    Operation::Mul => a.checked_mul(*b),
+                    _ => None
+                }
+            },
+            _ => None
+        }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let params: Vec<String> = self.params.iter()
+            .map(|(mode, value)| match mode {
+                ParameterMode::Position => format!("[{}]", value),
+                ParameterMode::Immediate => value.to_string(),
+                ParameterMode::Relative if *value < 0 => format!("[rb{}]", value),
+                ParameterMode::Relative => format!("[rb+{}]", value)
+            })
+            .collect();
+        write!(f, "{:>5}: {}", self.address, self.mnemonic())?;
+        if self.operation == Operation::Unknown {
+            write!(f, " {}", self.op_code)?;
+        } else if !params.is_empty() {
+            write!(f, "{}{}", " ".repeat(5 - self.mnemonic().len()), params.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EdgeKind {
+    Fallthrough,
+    Taken,
+    Call,
+    /// From a call to where the subroutine returns to.
+    ReturnSite,
+    /// Only seen by running the program.
+    Dynamic
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub start: usize,
+    pub instructions: Vec<Instruction>
+}
+
+impl Block {
+    fn last(&self) -> &Instruction {
+        self.instructions.last().expect("Blocks are never empty")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subroutine {
+    pub entry: usize,
+    /// Addresses of the jumps that call it.
+    pub callers: Vec<usize>,
+    pub blocks: Vec<usize>,
+    /// Addresses of the instructions it returns with.
+    pub returns: Vec<usize>,
+    /// Starts by moving the relative base, to make room for its locals.
+    pub adjusts_base: bool
+}
+
+pub struct Cfg {
+    pub blocks: BTreeMap<usize, Block>,
+    pub edges: BTreeSet<(usize, usize, EdgeKind)>,
+    pub subroutines: Vec<Subroutine>
+}
+
+impl Cfg {
+    /// Everything reachable from address 0 without running the program.
+    pub fn from_memory(memory: &[i64]) -> Cfg {
+        Cfg::with_dynamic(memory, &BTreeSet::new())
+    }
+
+    /// Also follows jumps seen while running, see `dynamic_jumps`.
+    pub fn with_dynamic(memory: &[i64], dynamic: &BTreeSet<(usize, usize)>) -> Cfg {
+        let mut instructions: BTreeMap<usize, Instruction> = BTreeMap::new();
+        let mut successors: Vec<(usize, usize, EdgeKind)> = vec![];
+        let mut leaders: BTreeSet<usize> = BTreeSet::new();
+        leaders.insert(0);
+
+        let mut pending: VecDeque<usize> = VecDeque::new();
+        pending.push_back(0);
+        while let Some(address) = pending.pop_front() {
+            if instructions.contains_key(&address) {
+                continue;
+            }
+            let instruction = match Instruction::decode(memory, address) {
+                Some(instruction) => instruction,
+                None => continue
+            };
+
+            let mut next = vec![];
+            if instruction.is_jump() {
+                let is_call = instruction.constant_condition() == Some(true)
+                    && instruction.static_target().is_some()
+                    && instructions.range(..address).next_back()
+                        .filter(|(_, previous)| previous.next() == address)
+                        .and_then(|(_, previous)| previous.pushed_constant()) == Some(instruction.next() as i64);
+                if let Some(target) = instruction.static_target() {
+                    if instruction.constant_condition() != Some(false) {
+                        next.push((target, if is_call { EdgeKind::Call } else { EdgeKind::Taken }));
+                    }
+                }
+                if is_call {
+                    next.push((instruction.next(), EdgeKind::ReturnSite));
+                } else if instruction.constant_condition() != Some(true) {
+                    next.push((instruction.next(), EdgeKind::Fallthrough));
+                }
+                for &(_, target) in dynamic.range((address, 0)..=(address, usize::MAX)) {
+                    if next.iter().all(|&(known, _)| known != target) {
+                        next.push((target, EdgeKind::Dynamic));
+                    }
+                }
+                leaders.extend(next.iter().map(|&(target, _)| target));
+            } else if !instruction.ends_flow() {
+                next.push((instruction.next(), EdgeKind::Fallthrough));
+            }
+
+            for &(target, kind) in &next {
+                successors.push((address, target, kind));
+                pending.push_back(target);
+            }
+            instructions.insert(address, instruction);
+        }
+
+        // Split the instructions into blocks at every leader and after every jump
+        let mut blocks: BTreeMap<usize, Block> = BTreeMap::new();
+        let mut current: Option<Block> = None;
+        for instruction in instructions.values() {
+            let continues = current.as_ref().is_some_and(|block| {
+                let last = block.last();
+                last.next() == instruction.address && !last.is_jump() && !last.ends_flow()
+                    && !leaders.contains(&instruction.address)
+            });
+            if !continues {
+                if let Some(block) = current.take() {
+                    blocks.insert(block.start, block);
+                }
+                current = Some(Block { start: instruction.address, instructions: vec![] });
+            }
+            current.as_mut().unwrap().instructions.push(instruction.clone());
+        }
+        if let Some(block) = current {
+            blocks.insert(block.start, block);
+        }
+
+        let block_of: BTreeMap<usize, usize> = blocks.values()
+            .flat_map(|block| block.instructions.iter().map(move |i| (i.address, block.start)))
+            .collect();
+        // Only the last instruction of a block leads anywhere else
+        let edges = successors.into_iter()
+            .filter(|(from, to, _)| blocks[&block_of[from]].last().address == *from && blocks.contains_key(to))
+            .map(|(from, to, kind)| (block_of[&from], to, kind))
+            .collect();
+
+        let mut cfg = Cfg { blocks, edges, subroutines: vec![] };
+        cfg.subroutines = cfg.find_subroutines();
+        cfg
+    }
+
+    fn find_subroutines(&self) -> Vec<Subroutine> {
+        let mut callers: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for &(from, to, kind) in &self.edges {
+            if kind == EdgeKind::Call {
+                callers.entry(to).or_default().push(self.blocks[&from].last().address);
+            }
+        }
+
+        callers.into_iter()
+            .map(|(entry, callers)| {
+                let mut blocks = BTreeSet::new();
+                let mut pending = vec![entry];
+                while let Some(start) = pending.pop() {
+                    if !blocks.insert(start) || self.blocks[&start].last().is_return() {
+                        continue;
+                    }
+                    pending.extend(self.edges.iter()
+                        .filter(|&&(from, _, kind)| from == start && kind != EdgeKind::Call && kind != EdgeKind::Dynamic)
+                        .map(|&(_, to, _)| to));
+                }
+                let returns = blocks.iter()
+                    .map(|start| self.blocks[start].last())
+                    .filter(|last| last.is_return())
+                    .map(|last| last.address)
+                    .collect();
+                let first = &self.blocks[&entry].instructions[0];
+                let adjusts_base = first.operation == Operation::AdjustBase
+                    && matches!(first.params[0], (ParameterMode::Immediate, n) if n > 0);
+                Subroutine { entry, callers, blocks: blocks.into_iter().collect(), returns, adjusts_base }
+            })
+            .collect()
+    }
+
+    /// Graphviz source, one box per block. Calls are dashed and jumps only
+    /// seen at runtime are dotted.
+    pub fn to_dot(&self) -> String {
+        let entries: BTreeSet<usize> = self.subroutines.iter().map(|s| s.entry).collect();
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+        for block in self.blocks.values() {
+            let label: String = block.instructions.iter().map(|i| format!("{}\\l", i)).collect();
+            let fill = if entries.contains(&block.start) { ", style=filled, fillcolor=lightblue" } else { "" };
+            dot.push_str(&format!("    b{} [label=\"{}\"{}];\n", block.start, label, fill));
+        }
+        for &(from, to, kind) in &self.edges {
+            let style = match kind {
+                EdgeKind::Fallthrough => "",
+                EdgeKind::Taken => " [label=\"taken\"]",
+                EdgeKind::Call => " [label=\"call\", style=dashed]",
+                EdgeKind::ReturnSite => " [label=\"returns\", style=dashed, color=gray]",
+                EdgeKind::Dynamic => " [style=dotted]"
+            };
+            dot.push_str(&format!("    b{} -> b{}{};\n", from, to, style));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// A disassembly listing of the reachable code, block by block.
+impl Display for Cfg {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for block in self.blocks.values() {
+            if let Some(subroutine) = self.subroutines.iter().find(|s| s.entry == block.start) {
+                writeln!(f, "; subroutine, called from {:?}", subroutine.callers)?;
+            }
+            writeln!(f, "block {}:", block.start)?;
+            for instruction in &block.instructions {
+                writeln!(f, "{}", instruction)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs a program that is ready to go and records every jump it takes, as
+/// (jump address, target), so jumps through memory can be added to the CFG.
+/// Fails if it doesn't halt within `max_steps`, as the jumps would be partial.
+pub fn dynamic_jumps<S: Default + Debug + Clone + 'static>(program: &mut Program<S>, max_steps: usize)
+    -> Result<BTreeSet<(usize, usize)>, Box<dyn Error>> {
+    let mut jumps = BTreeSet::new();
+    for _ in 0..max_steps {
+        let pointer = program.pointer();
+        let op_code = program.peek(pointer) % 100;
+        if program.step()? == Step::Halted {
+            return Ok(jumps);
+        }
+        if (op_code == JUMP_TRUE || op_code == JUMP_FALSE) && program.pointer() != pointer + 3 {
+            jumps.insert((pointer, program.pointer()));
+        }
+    }
+    Err(format!("Program didn't halt within {} steps", max_steps).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts up to 3 in address 20.
+    const LOOP : [i64; 15] = [
+        1007, 20, 3, 21,   // 0: mem[21] = mem[20] < 3
+        1006, 21, 14,      // 4: done if not
+        1001, 20, 1, 20,   // 7: mem[20] += 1
+        1105, 1, 0,        // 11: loop
+        99];
+
+    /// Calls a subroutine that increments address 60 twice, then outputs it.
+    const CALLS : [i64; 31] = [
+        109, 50,           // 0: rb = 50
+        21101, 9, 0, 0,    // 2: push return address 9
+        1105, 1, 20,       // 6: call
+        21101, 16, 0, 0,   // 9: push return address 16
+        1105, 1, 20,       // 13: call
+        4, 60,             // 16: output mem[60]
+        99, 0,             // 18
+        109, 1,            // 20: rb += 1
+        1001, 60, 1, 60,   // 22: mem[60] += 1
+        109, -1,           // 26: rb -= 1
+        2105, 1, 0];       // 28: return
+
+    #[test]
+    fn decodes() {
+        let instruction = Instruction::decode(&CALLS, 2).unwrap();
+        assert_eq!(instruction.to_string(), "    2: ADD  9, 0, [rb+0]");
+        assert_eq!(Instruction::decode(&CALLS, 28).unwrap().to_string(), "   28: JT   1, [rb+0]");
+        assert_eq!(Instruction::decode(&LOOP, 0).unwrap().to_string(), "    0: LT   [20], 3, [21]");
+        assert_eq!(Instruction::decode(&[0], 1), None);
+        for memory in &[&[0][..], &[1, 0, 0], &[30001, 0, 0, 0], &[42]] {
+            let data = Instruction::decode(memory, 0).unwrap();
+            assert_eq!((data.operation, data.next()), (Operation::Unknown, 1));
+        }
+        assert_eq!(Instruction::decode(&[42], 0).unwrap().to_string(), "    0: DATA 42");
+    }
+
+    #[test]
+    fn loop_blocks() {
+        let cfg = Cfg::from_memory(&LOOP);
+        assert_eq!(cfg.blocks.keys().cloned().collect::<Vec<_>>(), vec![0, 7, 14]);
+        let edges: Vec<_> = cfg.edges.iter().cloned().collect();
+        assert_eq!(edges, vec![
+            (0, 7, EdgeKind::Fallthrough),
+            (0, 14, EdgeKind::Taken),
+            (7, 0, EdgeKind::Taken)]);
+        assert!(cfg.subroutines.is_empty());
+    }
+
+    #[test]
+    fn finds_subroutines() {
+        let cfg = Cfg::from_memory(&CALLS);
+        assert_eq!(cfg.blocks.keys().cloned().collect::<Vec<_>>(), vec![0, 9, 16, 20]);
+        assert!(cfg.edges.contains(&(0, 20, EdgeKind::Call)));
+        assert!(cfg.edges.contains(&(9, 16, EdgeKind::ReturnSite)));
+        assert_eq!(cfg.subroutines, vec![Subroutine {
+            entry: 20, callers: vec![6, 13], blocks: vec![20], returns: vec![28], adjusts_base: true
+        }]);
+
+        let dot = cfg.to_dot();
+        assert!(dot.contains("    b0 -> b20 [label=\"call\", style=dashed];\n"));
+        assert!(dot.contains("b20 [label=\"   20: ARB  1\\l"));
+        assert!(cfg.to_string().contains("; subroutine, called from [6, 13]\nblock 20:\n"));
+    }
+
+    #[test]
+    fn follows_returns_at_runtime() {
+        let mut p : Program<()> = Program::new(&CALLS.to_vec());
+        let jumps = dynamic_jumps(&mut p, 1000).unwrap();
+        assert_eq!(jumps, vec![(6, 20), (13, 20), (28, 9), (28, 16)].into_iter().collect());
+
+        let cfg = Cfg::with_dynamic(&CALLS, &jumps);
+        assert!(cfg.edges.contains(&(20, 9, EdgeKind::Dynamic)));
+        assert!(cfg.edges.contains(&(20, 16, EdgeKind::Dynamic)));
+        assert!(!cfg.edges.contains(&(0, 20, EdgeKind::Dynamic)));
+        assert!(cfg.to_dot().contains("    b20 -> b16 [style=dotted];\n"));
+        assert_eq!(cfg.subroutines[0].blocks, vec![20]);
+
+        let mut p : Program<()> = Program::new(&CALLS.to_vec());
+        let error = dynamic_jumps(&mut p, 5).unwrap_err();
+        assert_eq!(error.to_string(), "Program didn't halt within 5 steps");
+    }
+
+    #[test]
+    fn finds_code_only_reached_at_runtime() {
+        // Jumps to the address stored at 7, which outputs 7
+        let program = vec![105, 1, 7, 99, 104, 7, 99, 4];
+        let cfg = Cfg::from_memory(&program);
+        assert_eq!(cfg.blocks.keys().cloned().collect::<Vec<_>>(), vec![0]);
+
+        let mut p : Program<()> = Program::new(&program);
+        let dynamic = dynamic_jumps(&mut p, 100).unwrap();
+        let cfg = Cfg::with_dynamic(&program, &dynamic);
+        assert_eq!(cfg.blocks.keys().cloned().collect::<Vec<_>>(), vec![0, 4]);
+        assert_eq!(cfg.edges.iter().cloned().collect::<Vec<_>>(), vec![(0, 4, EdgeKind::Dynamic)]);
+        assert_eq!(cfg.to_string(), "block 0:\n    0: JT   1, [7]\nblock 4:\n    4: OUT  7\n    6: HALT\n");
+    }
+}
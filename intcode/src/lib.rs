@@ -0,0 +1,13 @@
+//! The Intcode computer, shared by every day whose puzzle runs one, and the
+//! tools built on it.
+
+mod limits;
+mod opcode;
+
+pub mod ascii;
+pub mod network;
+pub mod analysis;
+pub mod cfg;
+
+pub use crate::limits::*;
+pub use crate::opcode::*;
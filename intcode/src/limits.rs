@@ -0,0 +1,140 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::time::{Duration, Instant};
+
+/// Bounds on a run, so a program that loops forever or wanders off into
+/// memory fails rather than hanging. None means unlimited.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Limits {
+    pub max_instructions: Option<u64>,
+    /// Highest address the program may write to or jump to.
+    pub max_address: Option<usize>,
+    pub timeout: Option<Duration>
+}
+
+/// Where the program was when a limit tripped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot<S> {
+    pub pointer: usize,
+    pub base_offset: i64,
+    pub executed: u64,
+    pub state: S
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LimitExceeded<S> {
+    Instructions { limit: u64, snapshot: Snapshot<S> },
+    Address { address: usize, limit: usize, snapshot: Snapshot<S> },
+    Timeout { limit: Duration, snapshot: Snapshot<S> }
+}
+
+impl <S> LimitExceeded<S> {
+    pub fn snapshot(&self) -> &Snapshot<S> {
+        match self {
+            LimitExceeded::Instructions { snapshot, .. } => snapshot,
+            LimitExceeded::Address { snapshot, .. } => snapshot,
+            LimitExceeded::Timeout { snapshot, .. } => snapshot
+        }
+    }
+}
+
+impl <S: Debug> Display for LimitExceeded<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitExceeded::Instructions { limit, .. } => write!(f, "Ran more than {} instructions", limit)?,
+            LimitExceeded::Address { address, limit, .. } => write!(f, "Address {} is past the limit of {}", address, limit)?,
+            LimitExceeded::Timeout { limit, .. } => write!(f, "Ran for longer than {:?}", limit)?
+        }
+        let snapshot = self.snapshot();
+        write!(f, " at {} after {} instructions, state {:?}", snapshot.pointer, snapshot.executed, snapshot.state)
+    }
+}
+
+impl <S: Debug> Error for LimitExceeded<S> {}
+
+/// Which limit a run went past. The VM adds a snapshot of itself to turn
+/// it into a `LimitExceeded`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Breach {
+    Instructions(u64),
+    Address { address: usize, limit: usize },
+    Timeout(Duration)
+}
+
+impl Breach {
+    pub fn with<S>(self, snapshot: Snapshot<S>) -> LimitExceeded<S> {
+        match self {
+            Breach::Instructions(limit) => LimitExceeded::Instructions { limit, snapshot },
+            Breach::Address { address, limit } => LimitExceeded::Address { address, limit, snapshot },
+            Breach::Timeout(limit) => LimitExceeded::Timeout { limit, snapshot }
+        }
+    }
+}
+
+/// Keeps count of a run so a VM can hold it to its `Limits`.
+#[derive(Debug, Clone, Default)]
+pub struct Meter {
+    limits: Limits,
+    executed: u64,
+    started: Option<Instant>
+}
+
+impl Meter {
+    pub fn new(limits: Limits) -> Meter {
+        Meter { limits, ..Meter::default() }
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// How many instructions have run, not counting HALT.
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    /// Checks the instruction at `pointer` may run, starting the clock on
+    /// the first call. A program may always halt, even on the last
+    /// instruction it's allowed.
+    pub fn check(&mut self, pointer: usize, halting: bool) -> Result<(), Breach> {
+        let started = *self.started.get_or_insert_with(Instant::now);
+        if let Some(limit) = self.limits.max_instructions.filter(|&limit| self.executed >= limit && !halting) {
+            return Err(Breach::Instructions(limit));
+        }
+        self.check_address(pointer)?;
+        if let Some(limit) = self.limits.timeout.filter(|&limit| started.elapsed() > limit) {
+            return Err(Breach::Timeout(limit));
+        }
+        Ok(())
+    }
+
+    /// Checks the program may write to or jump to `address`.
+    pub fn check_address(&self, address: usize) -> Result<(), Breach> {
+        match self.limits.max_address {
+            Some(limit) if address > limit => Err(Breach::Address { address, limit }),
+            _ => Ok(())
+        }
+    }
+
+    /// Counts an instruction that ran.
+    pub fn tick(&mut self) {
+        self.executed += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn meter() {
+        let mut meter = Meter::new(Limits { max_instructions: Some(1), max_address: Some(10), ..Limits::default() });
+        assert_eq!(meter.check(0, false), Ok(()));
+        meter.tick();
+        assert_eq!(meter.check(4, false), Err(Breach::Instructions(1)));
+        assert_eq!(meter.check(4, true), Ok(()));
+        assert_eq!(meter.check(11, true), Err(Breach::Address { address: 11, limit: 10 }));
+        assert_eq!(meter.check_address(10), Ok(()));
+        assert_eq!(meter.executed(), 1);
+    }
+}
@@ -0,0 +1,290 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+use crate::{Program, Step};
+
+/// Packets sent here go to the NAT rather than a machine.
+pub const NAT_ADDRESS : i64 = 255;
+/// What a machine reads when its queue is empty.
+const NO_PACKET : i64 = -1;
+/// How many empty reads in a row before a machine counts as idle.
+const IDLE_POLLS : usize = 2;
+/// Instructions a machine may run in one turn without touching its input.
+const MAX_SLICE : usize = 10_000;
+/// Turns to run before giving up on something happening.
+const MAX_TURNS : usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Packet {
+    pub from: i64,
+    pub to: i64,
+    pub x: i64,
+    pub y: i64
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Sent(Packet),
+    /// Sent to an address nobody is listening on.
+    Dropped(Packet),
+    /// Every machine was waiting on an empty queue.
+    Idle,
+    /// The NAT woke the network up by resending its last packet.
+    Resent(Packet)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    pub turn: usize,
+    pub event: Event
+}
+
+impl Display for TraceEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let packet = |f: &mut Formatter<'_>, label, p: &Packet| {
+            write!(f, "{:>6} {:<7} {:>3} -> {:<3} x={} y={}", self.turn, label, p.from, p.to, p.x, p.y)
+        };
+        match &self.event {
+            Event::Sent(p) => packet(f, "sent", p),
+            Event::Dropped(p) => packet(f, "dropped", p),
+            Event::Resent(p) => packet(f, "nat", p),
+            Event::Idle => write!(f, "{:>6} idle", self.turn)
+        }
+    }
+}
+
+/// A machine's input queue, shared with the input_fn of its program.
+#[derive(Debug, Default)]
+struct Inbox {
+    queue: VecDeque<i64>,
+    empty_polls: usize
+}
+
+impl Inbox {
+    fn read(&mut self) -> i64 {
+        match self.queue.pop_front() {
+            Some(value) => {
+                self.empty_polls = 0;
+                value
+            },
+            None => {
+                self.empty_polls += 1;
+                NO_PACKET
+            }
+        }
+    }
+}
+
+struct Machine {
+    program: Program<()>,
+    inbox: Rc<RefCell<Inbox>>,
+    outbox: Vec<i64>,
+    halted: bool
+}
+
+impl Machine {
+    fn is_idle(&self) -> bool {
+        let inbox = self.inbox.borrow();
+        self.halted || (inbox.queue.is_empty() && inbox.empty_polls >= IDLE_POLLS)
+    }
+}
+
+/// Many copies of one program, each booted with its own address, passing
+/// (address, x, y) packets to each other. Machines take turns in address
+/// order, each running until it reads its input, so runs are repeatable.
+pub struct Network {
+    machines: Vec<Machine>,
+    nat: Option<Packet>,
+    turn: usize,
+    trace: Vec<TraceEntry>
+}
+
+impl Network {
+    pub fn new(op_codes: &[i64], size: usize) -> Network {
+        let machines = (0..size)
+            .map(|address| {
+                let inbox = Rc::new(RefCell::new(Inbox::default()));
+                inbox.borrow_mut().queue.push_back(address as i64);
+
+                let mut program = Program::new(&op_codes.to_vec());
+                let reader = Rc::clone(&inbox);
+                program.set_input_fn(move |_| reader.borrow_mut().read());
+                Machine { program, inbox, outbox: vec![], halted: false }
+            })
+            .collect();
+
+        Network { machines, nat: None, turn: 0, trace: vec![] }
+    }
+
+    /// Every packet sent so far, and when the network went idle.
+    pub fn trace(&self) -> &[TraceEntry] {
+        &self.trace
+    }
+
+    /// The last packet the NAT received.
+    pub fn nat(&self) -> Option<Packet> {
+        self.nat
+    }
+
+    /// Queues a packet as though it came from outside the network.
+    pub fn inject(&mut self, to: i64, x: i64, y: i64) {
+        self.deliver(Packet { from: NAT_ADDRESS, to, x, y });
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.machines.iter().all(Machine::is_idle)
+    }
+
+    /// Gives every machine one turn, returning the packets sent.
+    pub fn turn(&mut self) -> Result<Vec<Packet>, Box<dyn Error>> {
+        let mut sent = vec![];
+        for address in 0..self.machines.len() {
+            sent.extend(self.run_machine(address)?);
+        }
+        for &packet in &sent {
+            self.deliver(packet);
+        }
+        self.turn += 1;
+        Ok(sent)
+    }
+
+    fn run_machine(&mut self, address: usize) -> Result<Vec<Packet>, Box<dyn Error>> {
+        let machine = &mut self.machines[address];
+        let mut sent = vec![];
+        for _ in 0..MAX_SLICE {
+            match machine.program.step()? {
+                Step::Halted => {
+                    machine.halted = true;
+                    break;
+                },
+                Step::Input(_) => break,
+                Step::Output(value) => {
+                    machine.outbox.push(value);
+                    if let [to, x, y] = machine.outbox[..] {
+                        machine.outbox.clear();
+                        machine.inbox.borrow_mut().empty_polls = 0;
+                        sent.push(Packet { from: address as i64, to, x, y });
+                    }
+                },
+                Step::Ran => {}
+            }
+        }
+        Ok(sent)
+    }
+
+    fn deliver(&mut self, packet: Packet) {
+        let event = if packet.to == NAT_ADDRESS {
+            self.nat = Some(packet);
+            Event::Sent(packet)
+        } else if let Some(machine) = self.machines.get(packet.to as usize).filter(|_| packet.to >= 0) {
+            machine.inbox.borrow_mut().queue.extend(&[packet.x, packet.y]);
+            if packet.from == NAT_ADDRESS { Event::Resent(packet) } else { Event::Sent(packet) }
+        } else {
+            Event::Dropped(packet)
+        };
+        self.record(event);
+    }
+
+    fn record(&mut self, event: Event) {
+        self.trace.push(TraceEntry { turn: self.turn, event });
+    }
+
+    /// Runs until a packet is sent to `address`.
+    pub fn first_packet_to(&mut self, address: i64) -> Result<Packet, Box<dyn Error>> {
+        for _ in 0..MAX_TURNS {
+            if let Some(packet) = self.turn()?.into_iter().find(|p| p.to == address) {
+                return Ok(packet);
+            }
+        }
+        Err(format!("No packet sent to {} after {} turns", address, MAX_TURNS).into())
+    }
+
+    /// Runs with the NAT watching for the network going idle, at which point
+    /// it resends its last packet to address 0. Stops at the first y value
+    /// the NAT sends twice in a row.
+    pub fn first_repeated_nat_y(&mut self) -> Result<i64, Box<dyn Error>> {
+        let mut last_y = None;
+        for _ in 0..MAX_TURNS {
+            self.turn()?;
+            if !self.is_idle() {
+                continue;
+            }
+            self.record(Event::Idle);
+            let packet = self.nat.ok_or("Network went idle before the NAT received anything")?;
+            if last_y == Some(packet.y) {
+                return Ok(packet.y);
+            }
+            last_y = Some(packet.y);
+            self.inject(0, packet.x, packet.y);
+        }
+        Err(format!("NAT didn't repeat itself after {} turns", MAX_TURNS).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends (address, address * 10) to the NAT, then polls its queue forever.
+    const ANNOUNCE : [i64; 18] = [
+        3, 100,
+        1002, 100, 10, 101,
+        104, 255, 4, 100, 4, 101,
+        3, 102,
+        1105, 1, 12,
+        99];
+
+    /// Forwards every packet it receives to the NAT.
+    const RELAY : [i64; 23] = [
+        3, 100,
+        3, 101,
+        1008, 101, -1, 102,
+        1005, 102, 2,
+        3, 103,
+        104, 255, 4, 101, 4, 103,
+        1105, 1, 2,
+        99];
+
+    #[test]
+    fn routes_to_the_nat() {
+        let mut network = Network::new(&ANNOUNCE, 3);
+        let packet = network.first_packet_to(NAT_ADDRESS).unwrap();
+        assert_eq!(packet, Packet { from: 0, to: 255, x: 0, y: 0 });
+    }
+
+    #[test]
+    fn relays_injected_packets() {
+        let mut network = Network::new(&RELAY, 2);
+        network.inject(1, 5, 6);
+        network.inject(7, 1, 1);
+        let packet = network.first_packet_to(NAT_ADDRESS).unwrap();
+        assert_eq!(packet, Packet { from: 1, to: 255, x: 5, y: 6 });
+        assert_eq!(network.trace()[1].event, Event::Dropped(Packet { from: 255, to: 7, x: 1, y: 1 }));
+    }
+
+    #[test]
+    fn nat_wakes_idle_network() {
+        let mut network = Network::new(&ANNOUNCE, 3);
+        assert_eq!(network.first_repeated_nat_y().unwrap(), 20);
+        assert_eq!(network.nat(), Some(Packet { from: 2, to: 255, x: 2, y: 20 }));
+
+        let events: Vec<&Event> = network.trace().iter().map(|t| &t.event).collect();
+        let resend = Event::Resent(Packet { from: 255, to: 0, x: 2, y: 20 });
+        assert_eq!(events[3..], [&Event::Idle, &resend, &Event::Idle]);
+        assert_eq!(network.trace()[0].to_string(), "     1 sent      0 -> 255 x=0 y=0");
+    }
+
+    #[test]
+    fn same_run_same_trace() {
+        let run = || {
+            let mut network = Network::new(&RELAY, 4);
+            network.inject(3, 1, 2);
+            network.inject(0, 3, 4);
+            network.first_repeated_nat_y().unwrap();
+            network.trace().to_vec()
+        };
+        assert_eq!(run(), run());
+    }
+}
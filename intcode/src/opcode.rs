@@ -0,0 +1,462 @@
+use std::error::Error;
+use crate::opcode::ParameterMode::{Position, Immediate, Relative};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use crate::limits::{Breach, Limits, Meter, Snapshot};
+
+type OpCodes = Vec<i64>;
+type InputFn<S> = Box<dyn Fn(&S) -> i64>;
+type OutputFn<S> = Box<dyn Fn(&S, i64) -> S>;
+
+pub struct Program<S> {
+    p : usize,
+    base_offset: i64,
+    memory: HashMap<usize, i64>,
+    image_len: usize,
+    last_write: Option<(usize, i64)>,
+    bad_write: Option<Breach>,
+    meter: Meter,
+    state: S,
+    input_fn: Option<InputFn<S>>,
+    output_fn: Option<OutputFn<S>>,
+}
+
+/// What a single instruction did.
+#[derive(PartialEq, Debug)]
+pub enum Step {
+    Ran,
+    Input(i64),
+    Output(i64),
+    Halted
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ParameterMode {
+    Position,
+    Immediate,
+    Relative
+}
+
+impl From<u32> for ParameterMode {
+    fn from(i: u32) -> Self {
+        match i {
+            0 => Position,
+            1 => Immediate,
+            2 => Relative,
+            _ => panic!("Unknown parameter mode")
+        }
+    }
+}
+
+/// Opcodes, once any parameter modes are taken off.
+pub const HALT : i64 = 99;
+pub const ADD : i64 = 1;
+pub const MUL : i64 = 2;
+pub const INPUT : i64 = 3;
+pub const OUTPUT : i64 = 4;
+pub const JUMP_TRUE : i64 = 5;
+pub const JUMP_FALSE : i64 = 6;
+pub const LESS_THAN : i64 = 7;
+pub const EQUALS : i64 = 8;
+pub const ADJUST_BASE_OFFSET : i64 = 9;
+
+/// How many memory cells an instruction takes up, opcode included, or None
+/// if the opcode isn't one we know.
+pub fn instruction_width(op_code: i64) -> Option<usize> {
+    match op_code % 100 {
+        HALT => Some(1),
+        INPUT | OUTPUT | ADJUST_BASE_OFFSET => Some(2),
+        JUMP_TRUE | JUMP_FALSE => Some(3),
+        ADD | MUL | LESS_THAN | EQUALS => Some(4),
+        _ => None
+    }
+}
+
+
+impl <S: Default + Debug + Clone + 'static> Program<S> {
+    pub fn new(op_codes: &OpCodes) -> Program<S> {
+        let memory : HashMap<usize,i64> = op_codes.iter()
+            .enumerate()
+            .fold(HashMap::new(), |acc, (i, op_code)| {
+                let mut acc = acc;
+                acc.entry(i).or_insert(*op_code);
+                acc
+            });
+
+        let state = S::default();
+
+        Program {
+            p: 0,
+            memory,
+            image_len: op_codes.len(),
+            last_write: None,
+            bad_write: None,
+            meter: Meter::default(),
+            state,
+            base_offset: 0,
+            input_fn: None,
+            output_fn: None
+        }
+    }
+
+    /// The value at `address`, which is 0 for memory the program never touched.
+    pub fn peek(&self, address: usize) -> i64 {
+        *self.memory.get(&address).unwrap_or(&0)
+    }
+
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// Address of the next instruction.
+    pub fn pointer(&self) -> usize {
+        self.p
+    }
+
+    /// How much memory the program was loaded into, as opposed to memory it
+    /// has used since.
+    pub fn image_len(&self) -> usize {
+        self.image_len
+    }
+
+    /// The address and value written by the last instruction, if it wrote anything.
+    pub fn last_write(&self) -> Option<(usize, i64)> {
+        self.last_write
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.meter.set_limits(limits);
+    }
+
+    /// How many instructions have run so far.
+    pub fn executed(&self) -> u64 {
+        self.meter.executed()
+    }
+
+    pub fn set_input_fn<F>(&mut self, f: F )
+        where F: Fn(&S) -> i64 + 'static {
+        self.input_fn = Some(Box::new(f));
+    }
+
+    pub fn set_output_fn<F>(&mut self, f: F )
+        where F: Fn(&S, i64) -> S + 'static {
+        self.output_fn = Some(Box::new(f));
+    }
+    
+    /// Runs until the program halts.
+    pub fn exec(&mut self) -> Result<&S, Box<dyn Error>> {
+        while self.step()? != Step::Halted {}
+
+        Ok(&self.state)
+    }
+
+    /// Executes a single instruction, so callers can pause between them.
+    pub fn step(&mut self) -> Result<Step, Box<dyn Error>> {
+        let halting = self.next_code() == HALT;
+        self.meter.check(self.p, halting).map_err(|breach| breach.with(self.snapshot()))?;
+        self.last_write = None;
+        let pointer = self.p;
+        let code = self.next_code();
+        let step = match code {
+            ADD   => { self.add(); Step::Ran },
+            MUL   => { self.mul(); Step::Ran },
+            INPUT => self.input().map_or(Step::Ran, Step::Input),
+            OUTPUT => Step::Output(self.output()),
+            JUMP_TRUE => { self.jump_if_true(); Step::Ran },
+            JUMP_FALSE => { self.jump_if_false(); Step::Ran },
+            LESS_THAN => { self.less_than(); Step::Ran },
+            EQUALS => { self.equals(); Step::Ran },
+            ADJUST_BASE_OFFSET => { self.adj_base_offset(); Step::Ran },
+            HALT  => Step::Halted,
+            _ => return Err(format!("Unknown OpCode {}", code).into())
+        };
+        if let Some(breach) = self.bad_write.take() {
+            // Report the instruction that tried to write, not the next one
+            self.p = pointer;
+            return Err(breach.with(self.snapshot()).into());
+        }
+        if step != Step::Halted {
+            self.meter.tick();
+        }
+
+        Ok(step)
+    }
+
+    fn snapshot(&self) -> Snapshot<S> {
+        Snapshot { pointer: self.p, base_offset: self.base_offset, executed: self.meter.executed(), state: self.state.clone() }
+    }
+    
+    fn input(&mut self) -> Option<i64> {
+        let value = self.input_fn.as_ref().map(|input_supplier| input_supplier(&self.state));
+        if let Some(value) = value {
+            self.update_param(value, 1);
+        }
+        self.p+=2;
+        value
+    }
+
+    fn output(&mut self) -> i64 {
+        let output_value = self.param1();
+        if let Some(f) = &self.output_fn {
+            self.state = f(&self.state, output_value);
+        }
+        self.p+=2;
+        output_value
+    }
+    
+    fn update_param(&mut self, value: i64, param: usize) {
+        let (_,p1,p2,p3) =  Self::decode(self.peek(self.p));
+        let param_mode = match param {
+            1 => p1,
+            2 => p2,
+            3 => p3,
+            _ => panic!("Can't calc param mode")
+        };
+        let index = self.p + param;
+        let literal_value = *self.memory.get(&index).unwrap_or(&0);
+        let address = match param_mode {
+            Position => literal_value as usize,
+            Relative => (self.base_offset + literal_value) as usize,
+            _ => panic!("Problem updating param1, unknown param mode")
+        };
+        if let Err(breach) = self.meter.check_address(address) {
+            self.bad_write = Some(breach);
+            return;
+        }
+        self.memory.insert(address, value);
+        self.last_write = Some((address, value));
+    }
+    
+    fn adj_base_offset(&mut self) {
+        self.base_offset += self.param1();
+        self.p+=2;
+    }
+
+    fn jump_if_true(&mut self) {
+        if self.param1() !=0 {
+            self.p = self.param2() as usize;
+        } else {
+            self.p +=3;
+        }
+    }
+
+    fn jump_if_false(&mut self) {
+        if self.param1() == 0 {
+            self.p = self.param2() as usize;
+        } else {
+            self.p += 3;
+        }
+    }
+
+    fn equals(&mut self) {
+        self.binary_op(|a, b| if a == b { 1 } else { 0 });
+    }
+
+    fn less_than(&mut self) {
+        self.binary_op(|a, b| if a < b { 1 } else { 0 });
+    }
+
+    fn add(&mut self) {
+        self.binary_op(|a, b| a + b);
+    }
+
+    fn mul(&mut self) {
+        self.binary_op(|a, b| a * b);
+    }
+
+    fn binary_op<F>(&mut self, f: F)
+        where F: Fn(i64, i64) -> i64 {
+        self.update_param(f(self.param1(), self.param2()), 3);
+        self.p+=4;
+    }
+
+    fn next_code(&mut self) -> i64 {
+        let (op_code, _ ,_ ,_ ) = Self::decode(self.peek(self.p));
+        op_code
+    }
+
+    fn decode(op_code: i64) -> (i64, ParameterMode, ParameterMode, ParameterMode) {
+        let data = format!("{:05}", op_code)
+            .chars()
+            .filter_map(|c| c.to_digit(10))
+            .collect::<Vec<u32>>();
+
+        let operation : i64  = format!("{}{}", &data[3], &data[4]).parse().unwrap();
+        let p1 = data[2].into();
+        let p2 = data[1].into();
+        let p3 = data[0].into();
+
+        (operation as i64 ,p1,p2,p3)
+    }
+
+    fn param1(&self) -> i64 {
+        let (_,p1,_,_) = Self::decode(self.peek(self.p));
+        self.resolve_param(p1, 1)
+    }
+
+    fn param2(&self) -> i64 {
+        let (_,_,p2,_) = Self::decode(self.peek(self.p));
+        self.resolve_param(p2, 2)
+    }
+    
+    fn resolve_param(&self, param_mode: ParameterMode, param_index: usize) -> i64 {
+        let (p, memory) = (self.p, &self.memory);
+        let index = p + param_index;
+        let literal_value = *memory.get(&index).unwrap_or(&0);
+        match param_mode {
+            Position => *memory.get(&(literal_value as usize)).unwrap_or(&0),
+            Immediate => literal_value,
+            Relative => *memory.get(&((self.base_offset + literal_value) as usize)).unwrap_or(&0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::time::Duration;
+    use crate::limits::LimitExceeded;
+    use conformance::{Outcome, run_suite, ALL_FEATURES};
+
+    fn run(op_codes: &[i64], inputs: &[i64]) -> (Program<Vec<i64>>, Vec<i64>) {
+        let mut p : Program<Vec<i64>> = Program::new(&op_codes.to_vec());
+        let inputs: Vec<i64> = inputs.to_vec();
+        let inputs = RefCell::new(inputs.into_iter());
+        p.set_input_fn(move |_| inputs.borrow_mut().next().expect("Ran out of input"));
+        p.set_output_fn(|outputs, output| {
+            let mut outputs = outputs.clone();
+            outputs.push(output);
+            outputs
+        });
+        let outputs = p.exec().expect("Program failed").clone();
+        (p, outputs)
+    }
+
+    #[test]
+    fn decode() {
+        let (code, op1, op2, op3) = Program::<()>::decode(1002);
+        assert_eq!(code, 2);
+        assert_eq!(op1, Position);
+        assert_eq!(op2, Immediate);
+        assert_eq!(op3, Position);
+    }
+
+    #[test]
+    fn program() {
+        let (p, _) = run(&[1002, 4, 3, 4, 33], &[]);
+        assert_eq!(p.peek(4), 99);
+    }
+    
+    #[test]
+    fn test_enum() {
+        let zero : ParameterMode = 0.into();
+        let one : ParameterMode = 1.into();
+        let two : ParameterMode = 2.into();
+
+        assert_eq!(zero, Position);
+        assert_eq!(one, Immediate);
+        assert_eq!(two, Relative);
+    }
+    
+    #[test]
+    fn test_base_pointer() {
+        let input = vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99];
+        let (_, outputs) = run(&input, &[]);
+        assert_eq!(outputs, input);
+    }
+    
+    #[test]
+    fn sixteen_digit() {
+        let input = vec![1102,34915192,34915192,7,4,7,99,0];
+        let (_, outputs) = run(&input, &[]);
+        assert_eq!(outputs, vec![1219070632396864]);
+        assert_eq!(outputs[0].to_string().len(), 16);
+    }
+    
+    #[test]
+    fn number() {
+        let input = vec![104,1125899906842624,99];
+        let (_, outputs) = run(&input, &[]);
+        assert_eq!(outputs, vec![1125899906842624]);
+    }
+
+    #[test]
+    fn steps() {
+        let mut p : Program<()> = Program::new(&vec![3, 9, 1001, 9, 1, 9, 4, 9, 99, 0]);
+        p.set_input_fn(|_| 41);
+        assert_eq!(p.step().unwrap(), Step::Input(41));
+        assert_eq!(p.last_write(), Some((9, 41)));
+        assert_eq!(p.step().unwrap(), Step::Ran);
+        assert_eq!(p.last_write(), Some((9, 42)));
+        assert_eq!(p.pointer(), 6);
+        assert_eq!(p.step().unwrap(), Step::Output(42));
+        assert_eq!(p.step().unwrap(), Step::Halted);
+        assert_eq!(p.step().unwrap(), Step::Halted);
+    }
+
+    fn limited(op_codes: Vec<i64>, limits: Limits) -> LimitExceeded<Vec<i64>> {
+        let mut p : Program<Vec<i64>> = Program::new(&op_codes);
+        p.set_output_fn(|outputs, output| {
+            let mut outputs = outputs.clone();
+            outputs.push(output);
+            outputs
+        });
+        p.set_limits(limits);
+        let error = p.exec().unwrap_err();
+        error.downcast_ref::<LimitExceeded<Vec<i64>>>().expect("Should be a limit").clone()
+    }
+
+    #[test]
+    fn instruction_limit() {
+        // Outputs 7 forever
+        let error = limited(vec![104, 7, 1105, 1, 0], Limits { max_instructions: Some(5), ..Limits::default() });
+        let snapshot = Snapshot { pointer: 2, base_offset: 0, executed: 5, state: vec![7, 7, 7] };
+        assert_eq!(error, LimitExceeded::Instructions { limit: 5, snapshot });
+        assert_eq!(error.to_string(), "Ran more than 5 instructions at 2 after 5 instructions, state [7, 7, 7]");
+    }
+
+    #[test]
+    fn address_limit() {
+        let limits = Limits { max_address: Some(100), ..Limits::default() };
+        let error = limited(vec![104, 1, 1101, 1, 1, 101, 99], limits.clone());
+        assert!(matches!(error, LimitExceeded::Address { address: 101, limit: 100, .. }));
+        assert_eq!(error.snapshot().pointer, 2);
+        assert_eq!(error.snapshot().state, vec![1]);
+
+        let error = limited(vec![1105, 1, 1000], limits.clone());
+        assert!(matches!(error, LimitExceeded::Address { address: 1000, .. }));
+
+        // Negative addresses wrap around to huge ones
+        let error = limited(vec![109, -5, 21101, 1, 1, 0, 99], limits);
+        assert_eq!(error.snapshot().base_offset, -5);
+    }
+
+    #[test]
+    fn timeout() {
+        let limits = Limits { timeout: Some(Duration::from_millis(20)), ..Limits::default() };
+        let error = limited(vec![1105, 1, 0], limits);
+        assert!(matches!(error, LimitExceeded::Timeout { .. }));
+        assert!(error.snapshot().executed > 0);
+    }
+
+    #[test]
+    fn within_limits() {
+        let mut p : Program<()> = Program::new(&vec![104, 1, 99]);
+        p.set_limits(Limits { max_instructions: Some(1), max_address: Some(2), timeout: Some(Duration::from_secs(1)) });
+        assert!(p.exec().is_ok());
+        assert_eq!(p.executed(), 1);
+    }
+
+    #[test]
+    fn conformance() {
+        let report = run_suite("Program<S>", |case| {
+            let (p, outputs) = run(case.program, case.inputs);
+            let memory = (0..case.program.len()).map(|address| p.peek(address)).collect();
+            Ok(Outcome { outputs: Some(outputs), last_output: None, memory: Some(memory) })
+        });
+        assert_eq!(report.failures(), Vec::<String>::new());
+        report.assert_supports(&ALL_FEATURES);
+    }
+}
+